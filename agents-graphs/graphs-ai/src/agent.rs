@@ -2,9 +2,10 @@ use graphs::Action;
 use log::debug;
 
 use crate::{
+    checks::has_messages,
     model::{ChatCompletionRequest, ModelClient},
-    state::ConversationState,
-    tool::{Tool, ToolDescription},
+    state::{ConversationState, ConversationUpdate},
+    tool::ToolDescription,
 };
 
 pub fn agent_node(
@@ -13,9 +14,9 @@ pub fn agent_node(
     tools: Vec<ToolDescription>,
 ) -> Action<ConversationState> {
     let model_name = model_name.into();
-    Action::new(
+    Action::update_fallible(
         "responding_agent",
-        Box::new(move |state| {
+        Box::new(move |state: &ConversationState| {
             // Here you can implement the logic for your agent
             // For example, you can modify the state or perform some actions
 
@@ -28,19 +29,20 @@ pub fn agent_node(
                 messages,
                 None,
                 None,
-                Some(tools.into_iter().map(|t| t.into()).collect()),
+                Some(tools.into_iter().map(Into::into).collect()),
                 None,
-            ));
+            ))?;
 
             debug!("Model response: {response:?}");
 
             let choices = response.choices;
             let response_message = &choices
                 .first()
-                .expect("expected at least one choice")
+                .ok_or("expected at least one choice")?
                 .message;
 
-            state.with_added_message(response_message.clone())
+            Ok(ConversationUpdate::message(response_message.clone()))
         }),
     )
+    .with_precondition(has_messages())
 }
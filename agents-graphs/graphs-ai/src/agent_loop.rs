@@ -0,0 +1,69 @@
+use graphs::{Graph, GraphRunner, NodePolicy, RunLimits, Subgraph};
+
+use crate::{
+    agent::agent_node,
+    model::ModelClient,
+    response_has_tools_node::response_has_tool_node,
+    state::ConversationState,
+    system_prompt_node::{SystemPromptLocation, add_system_prompt, remove_system_prompt},
+    tool::{Tool, ToolDescription},
+    tool_node::tool_node,
+};
+
+/// The usual agent loop as a single node: call the model, and while it asks for tools,
+/// invoke them and call the model again.
+///
+/// The system prompt is added before every model call, and removed again afterwards.
+/// Model calls and tool calls are retried with `retry`, and `limits` stops a model that keeps
+/// requesting tools. The fallback of `retry` names a node of the caller's graph, so it is not
+/// used inside the loop; set it on the node this returns instead.
+pub fn agent_loop(
+    model_name: impl Into<String>,
+    model: Box<dyn ModelClient>,
+    system_prompt: impl Into<String>,
+    tools: Vec<Box<dyn Tool>>,
+    retry: NodePolicy,
+    limits: RunLimits,
+) -> Subgraph<ConversationState> {
+    let retry = NodePolicy {
+        fallback: None,
+        ..retry
+    };
+
+    let tool_descriptions = tools
+        .iter()
+        .map(|tool| tool.get_full_description())
+        .collect::<Vec<ToolDescription>>();
+
+    let mut graph = Graph::new();
+
+    let remove_system_prompt_id = graph.register_node(remove_system_prompt());
+
+    graph
+        .start()
+        .then(remove_system_prompt_id)
+        .then(add_system_prompt(
+            system_prompt,
+            SystemPromptLocation::FirstMessage,
+        ))
+        .then(agent_node(model_name, model, tool_descriptions))
+        .with_policy(retry)
+        .branch(
+            response_has_tool_node(),
+            |graph| {
+                graph
+                    .then(tool_node(tools))
+                    .with_policy(retry)
+                    .then(remove_system_prompt_id); // loop back
+            },
+            |graph| {
+                graph.then(remove_system_prompt()).terminate();
+            },
+        );
+
+    let runner = GraphRunner::new(graph)
+        .expect("agent loop graph should be valid")
+        .with_limits(limits);
+
+    Subgraph::new("agent_loop", runner)
+}
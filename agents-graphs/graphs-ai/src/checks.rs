@@ -0,0 +1,38 @@
+use graphs::Condition;
+
+use crate::state::ConversationState;
+
+pub fn last_message_has_role(role: impl Into<String>) -> Condition<ConversationState> {
+    let role = role.into();
+
+    Condition::new(
+        format!("last_message_has_role({role})"),
+        Box::new(move |state: &ConversationState| {
+            state
+                .messages()
+                .last()
+                .is_some_and(|message| message.role == role)
+        }),
+    )
+}
+
+pub fn has_messages() -> Condition<ConversationState> {
+    Condition::new(
+        "has_messages",
+        Box::new(|state: &ConversationState| !state.messages().is_empty()),
+    )
+}
+
+/// Holds when the last message asks for tool calls that have not been answered yet.
+pub fn has_pending_tool_calls() -> Condition<ConversationState> {
+    Condition::new(
+        "has_pending_tool_calls",
+        Box::new(|state: &ConversationState| {
+            state
+                .messages()
+                .last()
+                .and_then(|message| message.tool_calls.as_ref())
+                .is_some_and(|calls| !calls.is_empty())
+        }),
+    )
+}
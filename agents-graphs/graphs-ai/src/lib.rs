@@ -1,4 +1,6 @@
 pub mod agent;
+pub mod agent_loop;
+pub mod checks;
 pub mod model;
 pub mod state;
 pub mod tool;
@@ -6,3 +8,4 @@ pub mod user;
 
 pub mod response_has_tools_node;
 pub mod system_prompt_node;
+pub mod tool_node;
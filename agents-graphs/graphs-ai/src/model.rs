@@ -1,8 +1,12 @@
 use crate::tool::ToolSchema;
+use graphs::BoxError;
 use serde::{Deserialize, Serialize};
 
-pub trait ModelClient {
-    fn get_model_response(&self, request: &ChatCompletionRequest) -> ChatCompletionResponse;
+pub trait ModelClient: Send + Sync {
+    fn get_model_response(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, BoxError>;
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,10 +13,7 @@ pub fn response_has_tool_node() -> Condition<ConversationState> {
 
             let tool_calls = &last_message.tool_calls;
 
-            match tool_calls {
-                Some(calls) => !calls.is_empty(),
-                None => false,
-            }
+            tool_calls.as_ref().is_some_and(|calls| !calls.is_empty())
         }),
     )
 }
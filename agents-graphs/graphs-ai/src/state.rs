@@ -1,3 +1,4 @@
+use graphs::{Reduce, append};
 use serde::{Deserialize, Serialize};
 
 use crate::model::Message;
@@ -43,3 +44,28 @@ impl Default for ConversationState {
         Self::new()
     }
 }
+
+/// A partial change to a `ConversationState`, for `Action::update`.
+#[derive(Debug, Clone, Default)]
+pub struct ConversationUpdate {
+    /// Added after the existing messages.
+    pub messages: Vec<Message>,
+}
+
+impl ConversationUpdate {
+    pub fn message(message: Message) -> Self {
+        Self {
+            messages: vec![message],
+        }
+    }
+}
+
+impl Reduce for ConversationState {
+    type Update = ConversationUpdate;
+
+    fn reduce(self, update: ConversationUpdate) -> Self {
+        Self {
+            messages: append(self.messages, update.messages),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::Debug;
 
+use graphs::BoxError;
 use log::info;
 use schemars::{JsonSchema, schema::SchemaObject, schema_for};
 use serde::{Deserialize, Serialize};
@@ -13,11 +14,11 @@ pub struct ToolDescription {
     pub parameters: ToolSchema,
 }
 
-pub trait Tool {
+pub trait Tool: Send + Sync {
     fn json_schema(&self) -> &ToolSchema;
     fn name(&self) -> &str;
     fn description(&self) -> &str;
-    fn get_output(&self, input_json: &str) -> String;
+    fn get_output(&self, input_json: &str) -> Result<String, BoxError>;
 
     fn get_full_description(&self) -> ToolDescription {
         ToolDescription {
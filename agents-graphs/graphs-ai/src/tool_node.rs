@@ -1,13 +1,49 @@
 use graphs::Action;
+use log::info;
 
 use crate::{
-    model::{ChatCompletionRequest, ModelClient},
+    checks::{has_pending_tool_calls, last_message_has_role},
+    model::Message,
     state::ConversationState,
     tool::Tool,
 };
 
-pub fn tool_node(
-    model: Box<dyn ModelClient>, // todo: expect some lazy model provider fn, not a model
-    tools: &[Box<dyn Tool>],
-) -> Action<ConversationState> {
+/// Invokes the first tool call of the last message, and adds the tool's output to the state.
+pub fn tool_node(available_tools: Vec<Box<dyn Tool>>) -> Action<ConversationState> {
+    Action::new_fallible(
+        "invoke_tool",
+        Box::new(move |state: ConversationState| {
+            let last_message = state
+                .messages()
+                .last()
+                .ok_or("expected at least one message")?;
+
+            let first_tool_call = last_message
+                .tool_calls
+                .as_ref()
+                .and_then(|calls| calls.first())
+                .ok_or("expected the last message to have a tool call")?;
+            let tool_call_id = first_tool_call.id.clone();
+
+            let tool = available_tools
+                .iter()
+                .find(|tool| tool.name() == first_tool_call.function.name)
+                .ok_or_else(|| format!("Tool not found: {}", first_tool_call.function.name))?;
+
+            info!("Invoking tool: {:?}", tool.name());
+
+            let output = tool.get_output(&first_tool_call.function.arguments)?;
+
+            let message = {
+                let mut message = Message::new("tool", output);
+                message.tool_call_id = Some(tool_call_id);
+
+                message
+            };
+
+            Ok(state.with_added_message(message))
+        }),
+    )
+    .with_precondition(has_pending_tool_calls())
+    .with_postcondition(last_message_has_role("tool"))
 }
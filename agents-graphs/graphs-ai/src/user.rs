@@ -1,4 +1,4 @@
-use graphs::Action;
+use graphs::{Action, Interrupt};
 
 use crate::{model::Message, state::ConversationState};
 
@@ -13,7 +13,18 @@ pub fn user_input_node() -> Action<ConversationState> {
                 .read_line(&mut input)
                 .expect("Failed to read line");
 
-            state.with_added_message(Message::new("user".to_string(), input.trim().to_string()))
+            add_user_input(state, input.trim())
         }),
     )
 }
+
+/// Suspends the run until the caller has the user's input, for callers that cannot block on stdin.
+///
+/// Resume with the input added to the interrupted state by `add_user_input`.
+pub fn user_input_interrupt() -> Interrupt {
+    Interrupt::new("user_input")
+}
+
+pub fn add_user_input(state: ConversationState, input: impl Into<String>) -> ConversationState {
+    state.with_added_message(Message::new("user".to_string(), input.into()))
+}
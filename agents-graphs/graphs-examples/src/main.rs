@@ -1,18 +1,16 @@
-mod invoke_tool;
 mod weather_tool;
 
-use graphs::GraphRunner;
+use std::time::Duration;
+
+use graphs::{GraphRunner, Interrupted, NodePolicy, RunContext, RunLimits, RunOutcome};
 use graphs_ai::{
-    agent::agent_node,
-    response_has_tools_node::response_has_tool_node,
+    agent_loop::agent_loop,
     state::ConversationState,
-    system_prompt_node::{SystemPromptLocation, add_system_prompt, remove_system_prompt},
-    tool::{Tool, ToolDescription},
-    user::user_input_node,
+    tool::Tool,
+    user::{add_user_input, user_input_interrupt},
 };
 use graphs_mcp::McpContext;
-use invoke_tool::invoke_tool;
-use log::info;
+use log::{debug, error, info};
 use openai_model::OpenAIModel;
 use weather_tool::WeatherTool;
 
@@ -54,64 +52,93 @@ fn main() {
             .collect::<Vec<Box<dyn Tool>>>()
     };
 
-    let tool_descriptions = tools
-        .iter()
-        .map(|tool| tool.get_full_description())
-        .collect::<Vec<ToolDescription>>();
-
     let mut graph = graphs::Graph::new();
 
-    let remove_system_prompt_id = graph.register_node(remove_system_prompt());
-
     graph
         .start()
-        .then(user_input_node())
-        // .then(remove_system_prompt())
-        .then(remove_system_prompt_id)
-        .then(add_system_prompt(
+        .then(user_input_interrupt())
+        .then(agent_loop(
+            model_name,
+            Box::new(model),
             "You are a helpful assistant. Do your best to help the user.",
-            SystemPromptLocation::FirstMessage,
-        ))
-        .then(agent_node(model_name, Box::new(model), tool_descriptions))
-        .branch(
-            response_has_tool_node(),
-            |graph| {
-                graph.then(invoke_tool(tools)).then(remove_system_prompt_id); // loop back
+            tools,
+            // model and MCP calls fail transiently
+            NodePolicy {
+                max_attempts: 3,
+                backoff: Duration::from_millis(500),
+                ..NodePolicy::default()
             },
-            |graph| graph.terminate(),
-        );
+            // a model that keeps requesting tools would otherwise loop forever
+            RunLimits {
+                max_visits_per_node: Some(10),
+                ..RunLimits::default()
+            },
+        ))
+        .terminate();
 
-    let runner = GraphRunner::new(graph);
+    debug!("agent graph:\n{}", graph.to_mermaid());
 
-    let mut state = ConversationState::new();
+    let runner = GraphRunner::new(graph).expect("agent graph should be valid");
 
-    loop {
-        state = runner.run(state);
-        let json_state = serde_json::to_string_pretty(&state).unwrap();
-        info!("next state: {json_state}");
+    // values for a single session, such as the user's own API key, go in its context
+    let context = RunContext::new();
 
-        let last_output = &state
-            .messages()
-            .last()
-            .expect("expected at least one message")
-            .content;
+    let mut result = runner.try_run(ConversationState::new(), &context);
 
-        info!("output: {last_output}");
+    loop {
+        result = match result {
+            Ok(RunOutcome::Completed(state)) => {
+                let json_state = serde_json::to_string_pretty(&state).unwrap();
+                info!("next state: {json_state}");
+
+                let last_output = &state
+                    .messages()
+                    .last()
+                    .expect("expected at least one message")
+                    .content;
+
+                info!("output: {last_output}");
+
+                runner.try_run(state, &context)
+            }
+            // every run waits for the user before calling the model
+            Ok(RunOutcome::Interrupted(interrupted)) => {
+                let input = read_user_input();
+
+                runner.resume_interrupted(
+                    Interrupted {
+                        state: add_user_input(interrupted.state, input),
+                        ..interrupted
+                    },
+                    &context,
+                )
+            }
+            Err(e) => {
+                error!("agent run failed: {e}");
+                runner.try_run(e.into_state(), &context)
+            }
+        };
     }
 }
 
+fn read_user_input() -> String {
+    let mut input = String::new();
+    println!("User:");
+    std::io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to read line");
+
+    input.trim().to_string()
+}
+
 #[cfg(test)]
 mod tests {
-    use graphs::{Action, Graph, GraphRunner};
+    use graphs::{Action, Graph, GraphRunner, RunContext, RunOutcome};
 
     fn adder(add: i32) -> Action<i32> {
         Action::new("adder", Box::new(move |x| x + add))
     }
 
-    fn subtractor(subtract: i32) -> Action<i32> {
-        Action::new("subtractor", Box::new(move |x| x - subtract))
-    }
-
     fn multiplier(multiply: i32) -> Action<i32> {
         Action::new("multiplier", Box::new(move |x| x * multiply))
     }
@@ -127,11 +154,11 @@ mod tests {
             .then(multiplier(3))
             .terminate();
 
-        let runner = GraphRunner::new(graph);
+        let runner = GraphRunner::new(graph).unwrap();
 
         // 3 + 1 + 1 + 2 * 3 = 21
-        let result = runner.run(3);
+        let result = runner.run(3, &RunContext::new());
 
-        assert_eq!(result, 18);
+        assert_eq!(result, RunOutcome::Completed(18));
     }
 }
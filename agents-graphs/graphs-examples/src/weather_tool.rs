@@ -1,6 +1,7 @@
+use graphs::BoxError;
 use graphs_ai::tool::{Tool, ToolSchema};
 use log::info;
-use schemars::{JsonSchema, schema::RootSchema, schema_for};
+use schemars::JsonSchema;
 
 pub struct WeatherTool {
     input_schema: ToolSchema,
@@ -8,12 +9,14 @@ pub struct WeatherTool {
 
 impl WeatherTool {
     pub fn new() -> Self {
-        WeatherTool {
+        Self {
             input_schema: ToolSchema::generate_schema::<WeatherToolParameters>(),
         }
     }
 }
 
+// Only used to generate the input schema; the fields are never read directly.
+#[allow(dead_code)]
 #[derive(JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct WeatherToolParameters {
@@ -29,16 +32,16 @@ impl Tool for WeatherTool {
         &self.input_schema
     }
 
-    fn name(&self) -> &str {
+    fn name(&self) -> &'static str {
         "weather_tool"
     }
 
-    fn description(&self) -> &str {
+    fn description(&self) -> &'static str {
         "gets the weather for a given city"
     }
 
-    fn get_output(&self, input_json: &str) -> String {
+    fn get_output(&self, input_json: &str) -> Result<String, BoxError> {
         info!("WeatherTool input JSON: {input_json}");
-        "it's 20 celcius and raining".into()
+        Ok("it's 20 celcius and raining".into())
     }
 }
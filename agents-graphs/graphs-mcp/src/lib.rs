@@ -1,6 +1,6 @@
 pub mod mcp_tool;
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use mcp_tool::McpTool;
 use rmcp::{
     RoleClient, ServiceExt,
@@ -46,8 +46,8 @@ impl McpContext {
             let converted = McpTool::new(
                 name.into(),
                 description.into(),
-                serde_json::to_string(schema.as_ref())?,
-                McpContext {
+                &serde_json::to_string(schema.as_ref())?,
+                Self {
                     client: Arc::clone(&self.client),
                 },
             );
@@ -60,7 +60,10 @@ impl McpContext {
 
     pub fn call_tool(&self, tool_name: &str, input_json: &str) -> Result<String> {
         let json: serde_json::Value = serde_json::from_str(input_json)?;
-        let json = json.as_object().unwrap().to_owned();
+        let json = json
+            .as_object()
+            .context("expected tool input to be a json object")?
+            .to_owned();
 
         let result = TOKIO_RT.block_on(async {
             self.client
@@ -71,9 +74,13 @@ impl McpContext {
                 .await
         })?;
 
-        let text = match result.content.first().unwrap().raw {
-            rmcp::model::RawContent::Text(ref t) => t,
-            _ => panic!("unexpected content"),
+        let content = result
+            .content
+            .first()
+            .context("expected tool result to have content")?;
+
+        let rmcp::model::RawContent::Text(ref text) = content.raw else {
+            return Err(anyhow!("unexpected content from tool {tool_name}"));
         };
 
         Ok(text.text.clone())
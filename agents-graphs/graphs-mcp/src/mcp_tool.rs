@@ -1,30 +1,21 @@
 use crate::McpContext;
-use graphs_ai::tool::{self, Tool};
-use schemars::JsonSchema;
-use serde_json::Value;
-use tracing::info;
+use graphs::BoxError;
+use graphs_ai::tool::Tool;
 
 pub struct McpTool {
     name: String,
     description: String,
-    input_schema: String,
     tool_schema: graphs_ai::tool::ToolSchema,
     context: McpContext,
 }
 
 impl McpTool {
-    pub fn new(
-        name: String,
-        description: String,
-        input_schema: String,
-        context: McpContext,
-    ) -> Self {
-        let tool_schema = Self::get_tool_schema(&input_schema);
+    pub fn new(name: String, description: String, input_schema: &str, context: McpContext) -> Self {
+        let tool_schema = Self::get_tool_schema(input_schema);
 
         Self {
             name,
             description,
-            input_schema,
             tool_schema,
             context,
         }
@@ -49,8 +40,8 @@ impl Tool for McpTool {
         &self.description
     }
 
-    fn get_output(&self, input_json: &str) -> String {
-        self.context.call_tool(&self.name, input_json).unwrap()
+    fn get_output(&self, input_json: &str) -> Result<String, BoxError> {
+        Ok(self.context.call_tool(&self.name, input_json)?)
     }
 }
 
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+
+use crate::{Graph, Node, NodeId};
+
+/// A cycle of nodes, starting at its lowest node id. The last node leads back to the first.
+pub type Cycle = Vec<NodeId>;
+
+/// Analysis of the paths a run can take through a graph.
+///
+/// Policy fallbacks and goto targets count as ways to continue, like edges.
+/// The graph does not have to be valid; edges to unknown nodes are followed no further.
+impl<T> Graph<T> {
+    /// The nodes that no run can reach, from the start node or the limits fallback,
+    /// ordered by node id. These are the nodes `validate` reports as unreachable.
+    pub fn unreachable_nodes(&self) -> Vec<NodeId> {
+        let reachable = self.reachable_from(self.roots());
+
+        self.sorted_node_ids()
+            .into_iter()
+            .filter(|node_id| !reachable.contains(node_id))
+            .collect()
+    }
+
+    /// Every cycle in the graph, such as the loop of an agent calling tools,
+    /// ordered by the node they start at.
+    pub fn cycles(&self) -> Vec<Cycle> {
+        let mut cycles = Vec::new();
+
+        for node_id in self.sorted_node_ids() {
+            let mut path = vec![node_id];
+            self.find_cycles(node_id, &mut path, &mut cycles);
+        }
+
+        cycles
+    }
+
+    /// The cycles a run can never leave to reach a terminal node, so a run entering one
+    /// only ends when it hits a limit.
+    pub fn non_terminating_cycles(&self) -> Vec<Cycle> {
+        let terminating = self.terminating_nodes();
+
+        // every node of a cycle reaches the others, so checking one is enough
+        self.cycles()
+            .into_iter()
+            .filter(|cycle| !terminating.contains(&cycle[0]))
+            .collect()
+    }
+
+    /// Every path from the start node to a terminal node that visits no node twice.
+    pub fn simple_paths(&self) -> Vec<Vec<NodeId>> {
+        let mut paths = Vec::new();
+
+        if self.nodes.contains_key(&self.start_id) {
+            let mut path = vec![self.start_id];
+            self.find_paths(&mut path, &mut paths);
+        }
+
+        paths
+    }
+
+    /// The successors of the node without duplicates, e.g. when both edges of a branch
+    /// lead to the same node.
+    fn distinct_successors(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut successors = Vec::new();
+
+        for next in self.successors(node_id) {
+            if !successors.contains(&next) {
+                successors.push(next);
+            }
+        }
+
+        successors
+    }
+
+    /// Extends `path` with nodes after its start, so each cycle is found once, from its lowest node.
+    fn find_cycles(&self, start: NodeId, path: &mut Vec<NodeId>, cycles: &mut Vec<Cycle>) {
+        let last = *path.last().expect("path starts with a node");
+
+        for next in self.distinct_successors(last) {
+            if next == start {
+                cycles.push(path.clone());
+            } else if next > start && !path.contains(&next) {
+                path.push(next);
+                self.find_cycles(start, path, cycles);
+                path.pop();
+            }
+        }
+    }
+
+    fn find_paths(&self, path: &mut Vec<NodeId>, paths: &mut Vec<Vec<NodeId>>) {
+        let last = *path.last().expect("path starts with a node");
+
+        // an edge to a node that is not in the graph leads nowhere; `validate` reports it
+        match self.nodes.get(&last).map(|n| &n.node) {
+            None => return,
+            Some(Node::Terminal) => {
+                paths.push(path.clone());
+                return;
+            }
+            Some(_) => {}
+        }
+
+        for next in self.distinct_successors(last) {
+            if !path.contains(&next) {
+                path.push(next);
+                self.find_paths(path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    /// The nodes with a path to a terminal node.
+    fn terminating_nodes(&self) -> HashSet<NodeId> {
+        let mut terminating = self
+            .sorted_node_ids()
+            .into_iter()
+            .filter(|node_id| matches!(self.node(*node_id), Node::Terminal))
+            .collect::<HashSet<_>>();
+
+        // walk backwards until no more nodes lead to a terminating one
+        loop {
+            let before = terminating.len();
+
+            for node_id in self.sorted_node_ids() {
+                if self
+                    .successors(node_id)
+                    .any(|next| terminating.contains(&next))
+                {
+                    terminating.insert(node_id);
+                }
+            }
+
+            if terminating.len() == before {
+                return terminating;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NodePolicy;
+    use crate::test_support::{adder, example_graph, multiplier, subtractor};
+
+    #[test]
+    fn analysis_finds_cycles_and_paths() {
+        let graph = example_graph();
+
+        assert!(graph.unreachable_nodes().is_empty());
+        assert_eq!(graph.cycles(), vec![vec![NodeId(1), NodeId(2)]]);
+        assert!(graph.non_terminating_cycles().is_empty());
+
+        let ids = |ids: &[usize]| ids.iter().copied().map(NodeId).collect::<Vec<_>>();
+        assert_eq!(
+            graph.simple_paths(),
+            vec![
+                ids(&[0, 1, 2, 3, 4, 5]),
+                ids(&[0, 1, 2, 3, 7, 8, 6, 9]),
+                ids(&[0, 1, 2, 3, 7, 6, 9]),
+            ]
+        );
+    }
+
+    #[test]
+    fn analysis_finds_non_terminating_cycles() {
+        let mut graph = Graph::new();
+
+        let loop_start = graph.register_node(adder(1));
+        let unused = graph.register_node(multiplier(2));
+        graph.continue_from(unused).terminate();
+
+        graph
+            .start()
+            .then(loop_start)
+            .then(subtractor(1))
+            .then(loop_start);
+
+        assert_eq!(graph.unreachable_nodes(), vec![unused, NodeId(3)]);
+        assert_eq!(
+            graph.non_terminating_cycles(),
+            vec![vec![loop_start, NodeId(4)]]
+        );
+        assert!(graph.simple_paths().is_empty());
+
+        // like `validate`, counts the limits fallback as a way in
+        graph.set_limits_fallback(unused);
+        assert!(graph.validate().is_ok());
+        assert!(graph.unreachable_nodes().is_empty());
+    }
+
+    #[test]
+    fn analysis_ignores_unknown_nodes() {
+        let mut graph = Graph::new();
+
+        // e.g. an id deserialized from elsewhere, or taken from another graph
+        let unknown = NodeId(99);
+        graph.start().then(adder(1)).then(unknown);
+        graph.set_policy(
+            NodeId(1),
+            NodePolicy {
+                fallback: Some(unknown),
+                ..NodePolicy::default()
+            },
+        );
+
+        assert!(graph.validate().is_err());
+        assert!(graph.unreachable_nodes().is_empty());
+        assert!(graph.cycles().is_empty());
+        assert!(graph.simple_paths().is_empty());
+    }
+}
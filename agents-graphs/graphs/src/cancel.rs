@@ -0,0 +1,197 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Debug, Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// A handle for stopping runs from outside, e.g. when a user disconnects or presses stop.
+///
+/// Clones share the same state, so keep one and put another in the run's `RunContext`.
+/// Async nodes can capture a clone, or read it from the context, and await `cancelled` to stop early.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    shared: Arc<Shared>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every run using this token. Cannot be undone.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = std::mem::take(&mut *self.lock_wakers());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+
+    fn lock_wakers(&self) -> std::sync::MutexGuard<'_, Vec<Waker>> {
+        self.shared
+            .wakers
+            .lock()
+            .expect("cancellation token lock poisoned")
+    }
+}
+
+/// The future returned by `CancellationToken::cancelled`.
+#[derive(Debug)]
+pub struct Cancelled {
+    token: CancellationToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        {
+            let mut wakers = self.token.lock_wakers();
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        }
+
+        // cancelled between the first check and registering the waker
+        if self.token.is_cancelled() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{adder, multiplier};
+    use crate::{Action, Graph, GraphRunner, RunContext, RunErrorKind, RunOutcome};
+
+    #[test]
+    fn cancelled_token_stops_run_between_nodes() {
+        let token = CancellationToken::new();
+        let stopper = token.clone();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(adder(1))
+            .then(Action::new(
+                "press_stop",
+                Box::new(move |x| {
+                    stopper.cancel();
+                    x
+                }),
+            ))
+            .then(multiplier(3))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let context = RunContext::new().with(token);
+        let err = runner.try_run(1, &context).unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::Cancelled));
+        assert_eq!(err.display_name(), "multiplier");
+        assert_eq!(err.into_state(), 2);
+
+        // the token belongs to that run, so later runs on the runner are not stopped
+        assert_eq!(
+            runner.try_run(1, &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+        assert_eq!(
+            runner
+                .try_run(1, &RunContext::new().with(CancellationToken::new()))
+                .unwrap(),
+            RunOutcome::Completed(6)
+        );
+    }
+
+    #[test]
+    fn cancelling_stops_pending_async_node() {
+        let token = CancellationToken::new();
+        let stopper = token.clone();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(adder(1))
+            .then(Action::new_async(
+                "waits_forever",
+                Box::new(move |_| {
+                    // cancelled from elsewhere while this node is waiting
+                    stopper.cancel();
+                    Box::pin(futures::future::pending())
+                }),
+            ))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let context = RunContext::new().with(token);
+        let err = runner.try_run(1, &context).unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::Cancelled));
+        assert_eq!(err.display_name(), "waits_forever");
+        assert_eq!(err.into_state(), 2);
+    }
+
+    #[test]
+    fn async_node_can_await_cancellation() {
+        let token = CancellationToken::new();
+        let watched = token.clone();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Action::new_async(
+                "stops_early",
+                Box::new(move |x| {
+                    let watched = watched.clone();
+                    Box::pin(async move {
+                        watched.cancelled().await;
+                        Ok(x)
+                    })
+                }),
+            ))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            token.cancel();
+        });
+
+        // the runner does not know about this token, so the node completes normally
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(1));
+        stopper.join().unwrap();
+    }
+}
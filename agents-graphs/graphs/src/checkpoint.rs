@@ -0,0 +1,390 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{BoxError, NodeId};
+
+/// A snapshot of a run, taken after a node completes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<T> {
+    pub run_id: String,
+    /// The node the run continues from.
+    pub node_id: NodeId,
+    pub state: T,
+    /// The number of steps taken so far, counted the same way as `RunLimits::max_steps`.
+    pub step: usize,
+}
+
+/// Somewhere to persist checkpoints, so a run can be resumed after a crash or restart.
+///
+/// Node ids are assigned in the order nodes are added, so a checkpoint can be resumed
+/// by any runner whose graph is built by the same code.
+pub trait CheckpointStore<T>: Send + Sync {
+    fn save(&self, checkpoint: &Checkpoint<T>) -> Result<(), BoxError>;
+
+    /// The most recently saved checkpoint of the run, if any.
+    fn latest(&self, run_id: &str) -> Result<Option<Checkpoint<T>>, BoxError>;
+
+    /// Every checkpoint of the run, oldest first.
+    ///
+    /// Stores that only keep the latest checkpoint return just that one.
+    fn history(&self, run_id: &str) -> Result<Vec<Checkpoint<T>>, BoxError> {
+        Ok(self.latest(run_id)?.into_iter().collect())
+    }
+}
+
+// Lets callers keep a handle to a store after giving it to a runner.
+impl<T, S: CheckpointStore<T> + ?Sized> CheckpointStore<T> for Arc<S> {
+    fn save(&self, checkpoint: &Checkpoint<T>) -> Result<(), BoxError> {
+        (**self).save(checkpoint)
+    }
+
+    fn latest(&self, run_id: &str) -> Result<Option<Checkpoint<T>>, BoxError> {
+        (**self).latest(run_id)
+    }
+
+    fn history(&self, run_id: &str) -> Result<Vec<Checkpoint<T>>, BoxError> {
+        (**self).history(run_id)
+    }
+}
+
+/// Keeps every checkpoint in memory, for tests and for runs that only need to survive errors.
+#[derive(Debug)]
+pub struct MemoryCheckpointStore<T> {
+    runs: Mutex<HashMap<String, Vec<Checkpoint<T>>>>,
+}
+
+impl<T> MemoryCheckpointStore<T> {
+    pub fn new() -> Self {
+        Self {
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> Default for MemoryCheckpointStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone + Send> CheckpointStore<T> for MemoryCheckpointStore<T> {
+    fn save(&self, checkpoint: &Checkpoint<T>) -> Result<(), BoxError> {
+        self.runs
+            .lock()
+            .expect("checkpoint store lock poisoned")
+            .entry(checkpoint.run_id.clone())
+            .or_default()
+            .push(checkpoint.clone());
+
+        Ok(())
+    }
+
+    fn latest(&self, run_id: &str) -> Result<Option<Checkpoint<T>>, BoxError> {
+        Ok(self
+            .runs
+            .lock()
+            .expect("checkpoint store lock poisoned")
+            .get(run_id)
+            .and_then(|checkpoints| checkpoints.last().cloned()))
+    }
+
+    fn history(&self, run_id: &str) -> Result<Vec<Checkpoint<T>>, BoxError> {
+        Ok(self
+            .runs
+            .lock()
+            .expect("checkpoint store lock poisoned")
+            .get(run_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// Appends checkpoints to one JSON Lines file per run, named after the run id.
+///
+/// Run ids are percent-encoded in file names, so any id stays inside the directory.
+#[derive(Debug, Clone)]
+pub struct JsonCheckpointStore {
+    dir: PathBuf,
+}
+
+impl JsonCheckpointStore {
+    /// Stores checkpoints under `dir`, which is created on first save if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, run_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", file_name(run_id)))
+    }
+
+    /// The contents of the run's file, which is empty if nothing was saved yet.
+    fn read(&self, run_id: &str) -> Result<String, BoxError> {
+        match fs::read_to_string(self.path(run_id)) {
+            Ok(contents) => Ok(contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Encodes every byte of the run id but ASCII letters, digits, `-` and `_`, so ids such as
+/// `../x` or `a/b` cannot name a file outside the store's directory.
+fn file_name(run_id: &str) -> String {
+    let mut name = String::with_capacity(run_id.len());
+
+    for byte in run_id.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            name.push(char::from(byte));
+        } else {
+            let _ = write!(name, "%{byte:02X}");
+        }
+    }
+
+    name
+}
+
+impl<T: Serialize + DeserializeOwned> CheckpointStore<T> for JsonCheckpointStore {
+    fn save(&self, checkpoint: &Checkpoint<T>) -> Result<(), BoxError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut line = serde_json::to_string(checkpoint)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(&checkpoint.run_id))?
+            .write_all(line.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn latest(&self, run_id: &str) -> Result<Option<Checkpoint<T>>, BoxError> {
+        self.read(run_id)?
+            .lines()
+            .rfind(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn history(&self, run_id: &str) -> Result<Vec<Checkpoint<T>>, BoxError> {
+        self.read(run_id)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::test_support::{counting_loop, flaky_graph, multiplier};
+    use crate::{Graph, GraphRunner, Node, ResumeError, RunContext, RunLimits, RunOutcome};
+
+    #[test]
+    fn resume_continues_from_last_checkpoint() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let store = std::sync::Arc::new(MemoryCheckpointStore::new());
+
+        let runner = GraphRunner::new(flaky_graph(&failing))
+            .unwrap()
+            .with_checkpoints(Box::new(store.clone()));
+
+        let err = runner
+            .try_run_with_id("run-1", 1, &RunContext::new())
+            .unwrap_err();
+        assert_eq!(err.display_name(), "flaky");
+
+        // START and adder have run, and flaky is next
+        assert_eq!(
+            store.latest("run-1").unwrap(),
+            Some(Checkpoint {
+                run_id: "run-1".to_string(),
+                node_id: NodeId(2),
+                state: 2,
+                step: 2,
+            })
+        );
+
+        failing.store(false, Ordering::SeqCst);
+
+        assert_eq!(
+            runner.resume("run-1", &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+    }
+
+    #[test]
+    fn json_store_resumes_in_new_runner() {
+        let dir = std::env::temp_dir().join(format!("graphs-checkpoints-{}", std::process::id()));
+        let failing = Arc::new(AtomicBool::new(true));
+
+        let runner = GraphRunner::new(flaky_graph(&failing))
+            .unwrap()
+            .with_checkpoints(Box::new(JsonCheckpointStore::new(&dir)));
+        assert!(
+            runner
+                .try_run_with_id("run-1", 1, &RunContext::new())
+                .is_err()
+        );
+
+        // a rebuilt graph has the same node ids, so the checkpoint carries over
+        failing.store(false, Ordering::SeqCst);
+        let runner = GraphRunner::new(flaky_graph(&failing))
+            .unwrap()
+            .with_checkpoints(Box::new(JsonCheckpointStore::new(&dir)));
+
+        assert_eq!(
+            runner.resume("run-1", &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        let steps = runner
+            .history("run-1")
+            .unwrap()
+            .iter()
+            .map(|c| c.step)
+            .collect::<Vec<_>>();
+        assert_eq!(steps, [1, 2, 3, 4]);
+
+        // the completed run resumes at its terminal
+        assert_eq!(
+            runner.resume("run-1", &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        // run ids cannot name files outside the directory
+        runner
+            .try_run_with_id("../run-2", 1, &RunContext::new())
+            .unwrap();
+        assert!(dir.join("%2E%2E%2Frun-2.jsonl").exists());
+        assert_eq!(
+            runner.resume("../run-2", &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fork_reruns_from_edited_checkpoint() {
+        let failing = Arc::new(AtomicBool::new(false));
+
+        let runner = GraphRunner::new(flaky_graph(&failing))
+            .unwrap()
+            .with_checkpoints(Box::new(MemoryCheckpointStore::new()));
+
+        assert_eq!(
+            runner
+                .try_run_with_id("run-1", 1, &RunContext::new())
+                .unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        let original = runner.history("run-1").unwrap();
+
+        // what if adder had produced 10 instead of 2?
+        let mut checkpoint = original[1].clone();
+        assert_eq!((checkpoint.node_id, checkpoint.state), (NodeId(2), 2));
+        checkpoint.state = 10;
+
+        assert_eq!(
+            runner
+                .fork(checkpoint.clone(), "run-2", &RunContext::new())
+                .unwrap(),
+            RunOutcome::Completed(30)
+        );
+
+        let forked = runner.history("run-2").unwrap();
+        let states = forked.iter().map(|c| c.state).collect::<Vec<_>>();
+        assert_eq!(states, [1, 10, 10, 30]);
+        assert!(forked.iter().all(|c| c.run_id == "run-2"));
+
+        assert_eq!(runner.history("run-1").unwrap(), original);
+
+        assert!(matches!(
+            runner.fork(checkpoint, "run-1", &RunContext::new()),
+            Err(ResumeError::RunExists(run_id)) if run_id == "run-1"
+        ));
+    }
+
+    #[test]
+    fn fork_copies_history_from_before_the_limits_fallback() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let fallback = graph.register_node(multiplier(-1));
+        graph.add_node_from(fallback, Node::Terminal);
+        graph.set_limits_fallback(fallback);
+
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_limits(RunLimits {
+                max_steps: Some(5),
+                ..RunLimits::default()
+            })
+            .with_checkpoints(Box::new(MemoryCheckpointStore::new()));
+
+        assert_eq!(
+            runner
+                .try_run_with_id("run-1", 0, &RunContext::new())
+                .unwrap(),
+            RunOutcome::Completed(-2)
+        );
+
+        let steps = |run_id| {
+            runner
+                .history(run_id)
+                .unwrap()
+                .iter()
+                .map(|c| c.step)
+                .collect::<Vec<_>>()
+        };
+
+        // the fallback starts counting again
+        assert_eq!(steps("run-1"), [1, 2, 3, 4, 5, 1]);
+
+        let mut checkpoint = runner.history("run-1").unwrap().pop().unwrap();
+        checkpoint.state = 7;
+
+        assert_eq!(
+            runner
+                .fork(checkpoint, "run-2", &RunContext::new())
+                .unwrap(),
+            RunOutcome::Completed(7)
+        );
+        assert_eq!(steps("run-2"), [1, 2, 3, 4, 5, 1]);
+    }
+
+    #[test]
+    fn resume_reports_missing_checkpoint() {
+        let failing = Arc::new(AtomicBool::new(false));
+
+        let runner = GraphRunner::new(flaky_graph(&failing))
+            .unwrap()
+            .with_checkpoints(Box::new(MemoryCheckpointStore::new()));
+
+        assert_eq!(
+            runner.try_run(1, &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        // runs without an id are not checkpointed
+        assert!(matches!(
+            runner.resume("run-1", &RunContext::new()),
+            Err(ResumeError::NoCheckpoint(run_id)) if run_id == "run-1"
+        ));
+    }
+}
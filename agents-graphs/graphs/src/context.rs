@@ -0,0 +1,184 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::BTreeMap,
+    fmt::Debug,
+};
+
+use crate::BoxError;
+
+/// Values that belong to a single run, such as the user a request is for or a request-scoped
+/// API key, passed by reference to contextual actions, conditions and routers.
+///
+/// Values are looked up by type, so wrap plain strings and numbers in a type of their own.
+/// A `CancellationToken` in the context stops the run once it is cancelled.
+#[derive(Default)]
+pub struct RunContext {
+    values: BTreeMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Debug for RunContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunContext")
+            .field("values", &self.values.len())
+            .finish()
+    }
+}
+
+impl RunContext {
+    pub const fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the value, replacing any earlier value of the same type.
+    pub fn with<V: Any + Send + Sync>(mut self, value: V) -> Self {
+        self.values.insert(TypeId::of::<V>(), Box::new(value));
+        self
+    }
+
+    pub fn get<V: Any>(&self) -> Option<&V> {
+        self.values
+            .get(&TypeId::of::<V>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Like `get`, failing with an error that names the missing type, for use with `?`
+    /// in contextual actions and conditions.
+    pub fn require<V: Any>(&self) -> Result<&V, BoxError> {
+        self.get()
+            .ok_or_else(|| format!("run context has no {}", type_name::<V>()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::multiplier;
+    use crate::{
+        Action, Condition, Graph, GraphRunner, Interrupt, Router, RunErrorKind, RunOutcome,
+        Subgraph,
+    };
+
+    struct Bonus(i32);
+
+    fn bonus_adder() -> Action<i32> {
+        Action::new_contextual(
+            "bonus_adder",
+            Box::new(|x, context| Ok(x + context.require::<Bonus>()?.0)),
+        )
+    }
+
+    #[test]
+    fn contextual_nodes_read_the_run_context() {
+        let mut graph = Graph::new();
+
+        graph.start().then(bonus_adder()).branch(
+            Condition::new_contextual(
+                "over bonus",
+                Box::new(|&x, context| Ok(x > context.require::<Bonus>()?.0)),
+            ),
+            |graph| {
+                graph.terminate();
+            },
+            |graph| {
+                graph.then(multiplier(2)).terminate();
+            },
+        );
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // one graph, different context per run
+        assert_eq!(
+            runner.run(1, &RunContext::new().with(Bonus(10))),
+            RunOutcome::Completed(11)
+        );
+        assert_eq!(
+            runner.run(-5, &RunContext::new().with(Bonus(3))),
+            RunOutcome::Completed(-4)
+        );
+    }
+
+    #[test]
+    fn every_kind_of_node_can_read_the_run_context() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(Interrupt::new("wait"))
+            .then(Action::new_async_contextual(
+                "async_bonus_adder",
+                Box::new(|x, context| {
+                    Box::pin(async move { Ok(x + context.require::<Bonus>()?.0) })
+                }),
+            ))
+            .branch(
+                Condition::new_async_contextual(
+                    "async over bonus",
+                    Box::new(|&x, context| {
+                        Box::pin(async move { Ok(x > context.require::<Bonus>()?.0) })
+                    }),
+                ),
+                |graph| {
+                    graph
+                        .route(Router::new_contextual(
+                            "by bonus",
+                            Box::new(|_, context| {
+                                context.get::<Bonus>().map_or("none", |_| "bonus")
+                            }),
+                        ))
+                        .on("bonus", |graph| graph.then(multiplier(2)).terminate())
+                        .on("none", |graph| {
+                            graph.terminate();
+                        });
+                },
+                |graph| {
+                    graph.terminate();
+                },
+            );
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let context = RunContext::new().with(Bonus(3));
+
+        let Ok(RunOutcome::Interrupted(interrupted)) = runner.try_run(1, &context) else {
+            panic!("run should stop at the interrupt");
+        };
+
+        // a resumed run is given the context again
+        assert_eq!(
+            runner.resume_interrupted(interrupted, &context).unwrap(),
+            RunOutcome::Completed(8)
+        );
+    }
+
+    #[test]
+    fn missing_context_values_fail_the_node() {
+        let mut graph = Graph::new();
+
+        graph.start().then(bonus_adder()).terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::Action(e) if e.to_string().contains("Bonus")));
+    }
+
+    #[test]
+    fn subgraphs_share_the_run_context() {
+        let mut inner = Graph::new();
+        inner.start().then(bonus_adder()).terminate();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Subgraph::new("inner", GraphRunner::new(inner).unwrap()))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(
+            runner.run(1, &RunContext::new().with(Bonus(2))),
+            RunOutcome::Completed(3)
+        );
+    }
+}
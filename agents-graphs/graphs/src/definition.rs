@@ -0,0 +1,556 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Action, Condition, Edge, Graph, IdentifiedNode, Interrupt, Join, Node, NodeId, Router, Subgraph,
+};
+
+/// A serializable description of a graph, for keeping workflows in config files.
+///
+/// Nodes refer to actions, conditions, routers, joins and subgraphs by name, which are
+/// looked up in a `Registry` when the definition is loaded with `Graph::from_definition`.
+/// Node policies and the limits fallback are not part of the definition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphDefinition {
+    pub nodes: Vec<NodeDefinition>,
+    pub edges: Vec<EdgeDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDefinition {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NodeKind {
+    Start,
+    Action {
+        action: String,
+    },
+    Branch {
+        condition: String,
+    },
+    Router {
+        router: String,
+    },
+    /// `join` is the id of the join node the paths of the fan-out end at.
+    FanOut {
+        join: String,
+    },
+    Join {
+        merge: String,
+    },
+    Interrupt {
+        name: String,
+    },
+    Subgraph {
+        subgraph: String,
+    },
+    Terminal,
+}
+
+/// An edge between two nodes, by id.
+///
+/// Edges leaving a branch have the key `true` or `false`, and edges leaving a router
+/// have the key they are taken for. Other edges have no key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeDefinition {
+    pub from: String,
+    pub to: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+type Factory<N> = Box<dyn Fn() -> N + Send + Sync>;
+
+/// The named building blocks a `GraphDefinition` can refer to.
+///
+/// Definitions emitted by `Graph::to_definition` refer to nodes by display name,
+/// so register each under its display name to load them back.
+pub struct Registry<T> {
+    actions: HashMap<String, Factory<Action<T>>>,
+    conditions: HashMap<String, Factory<Condition<T>>>,
+    routers: HashMap<String, Factory<Router<T>>>,
+    joins: HashMap<String, Factory<Join<T>>>,
+    subgraphs: HashMap<String, Factory<Subgraph<T>>>,
+}
+
+impl<T> std::fmt::Debug for Registry<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registry")
+            .field("actions", &self.actions.keys())
+            .field("conditions", &self.conditions.keys())
+            .field("routers", &self.routers.keys())
+            .field("joins", &self.joins.keys())
+            .field("subgraphs", &self.subgraphs.keys())
+            .finish()
+    }
+}
+
+impl<T> Registry<T> {
+    pub fn new() -> Self {
+        Self {
+            actions: HashMap::new(),
+            conditions: HashMap::new(),
+            routers: HashMap::new(),
+            joins: HashMap::new(),
+            subgraphs: HashMap::new(),
+        }
+    }
+
+    /// Registers a function that creates the action, called once for every node that uses it.
+    pub fn with_action(mut self, name: impl Into<String>, factory: Factory<Action<T>>) -> Self {
+        self.actions.insert(name.into(), factory);
+        self
+    }
+
+    pub fn with_condition(
+        mut self,
+        name: impl Into<String>,
+        factory: Factory<Condition<T>>,
+    ) -> Self {
+        self.conditions.insert(name.into(), factory);
+        self
+    }
+
+    pub fn with_router(mut self, name: impl Into<String>, factory: Factory<Router<T>>) -> Self {
+        self.routers.insert(name.into(), factory);
+        self
+    }
+
+    pub fn with_join(mut self, name: impl Into<String>, factory: Factory<Join<T>>) -> Self {
+        self.joins.insert(name.into(), factory);
+        self
+    }
+
+    pub fn with_subgraph(mut self, name: impl Into<String>, factory: Factory<Subgraph<T>>) -> Self {
+        self.subgraphs.insert(name.into(), factory);
+        self
+    }
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reason a `GraphDefinition` could not be loaded.
+///
+/// Only problems that stop the graph from being built are reported here;
+/// check the loaded graph with `Graph::validate` for the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefinitionError {
+    /// Two nodes have the same id.
+    DuplicateNode(String),
+    /// An edge or fan-out refers to a node id that is not defined.
+    UnknownNode(String),
+    /// A node refers to a name that is not in the registry.
+    Unregistered { kind: &'static str, name: String },
+    /// An edge has a key that does not fit the node it leaves.
+    InvalidEdgeKey { from: String, key: Option<String> },
+    /// Two edges leave a branch or router with the same key.
+    DuplicateEdgeKey { from: String, key: String },
+}
+
+impl Display for DefinitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateNode(id) => write!(f, "node {id} is defined more than once"),
+            Self::UnknownNode(id) => write!(f, "node {id} is not defined"),
+            Self::Unregistered { kind, name } => write!(f, "no {kind} named {name} is registered"),
+            Self::InvalidEdgeKey {
+                from,
+                key: Some(key),
+            } => {
+                write!(f, "edge from {from} cannot have key {key}")
+            }
+            Self::InvalidEdgeKey { from, key: None } => write!(f, "edge from {from} needs a key"),
+            Self::DuplicateEdgeKey { from, key } => {
+                write!(f, "more than one edge from {from} has key {key}")
+            }
+        }
+    }
+}
+
+impl Error for DefinitionError {}
+
+fn lookup<N>(
+    factories: &HashMap<String, Factory<N>>,
+    kind: &'static str,
+    name: &str,
+) -> Result<N, DefinitionError> {
+    factories
+        .get(name)
+        .map(|factory| factory())
+        .ok_or_else(|| DefinitionError::Unregistered {
+            kind,
+            name: name.to_string(),
+        })
+}
+
+impl<T> Graph<T> {
+    /// Builds a graph from its definition, creating nodes in the order they are defined.
+    ///
+    /// Building the same definition twice gives the same node ids, so checkpoints carry over.
+    pub fn from_definition(
+        definition: &GraphDefinition,
+        registry: &Registry<T>,
+    ) -> Result<Self, DefinitionError> {
+        let mut graph = Self::new();
+        let mut ids = HashMap::new();
+        let mut fan_outs = Vec::new();
+
+        for node in &definition.nodes {
+            let node_id = match &node.kind {
+                NodeKind::Start => {
+                    graph.start();
+                    graph.start_id
+                }
+                NodeKind::Action { action } => {
+                    graph.register_node(lookup(&registry.actions, "action", action)?)
+                }
+                NodeKind::Branch { condition } => {
+                    graph.register_node(lookup(&registry.conditions, "condition", condition)?)
+                }
+                NodeKind::Router { router } => {
+                    graph.register_node(lookup(&registry.routers, "router", router)?)
+                }
+                NodeKind::FanOut { join } => {
+                    // the join may be defined later, so it is filled in once every node exists
+                    let fan_out_id = graph.register_node(Node::FanOut {
+                        join: graph.start_id,
+                    });
+                    fan_outs.push((fan_out_id, join));
+                    fan_out_id
+                }
+                NodeKind::Join { merge } => {
+                    graph.register_node(lookup(&registry.joins, "join", merge)?)
+                }
+                NodeKind::Interrupt { name } => graph.register_node(Interrupt::new(name)),
+                NodeKind::Subgraph { subgraph } => {
+                    graph.register_node(lookup(&registry.subgraphs, "subgraph", subgraph)?)
+                }
+                NodeKind::Terminal => graph.register_node(Node::Terminal),
+            };
+
+            if ids.insert(node.id.as_str(), node_id).is_some() {
+                return Err(DefinitionError::DuplicateNode(node.id.clone()));
+            }
+        }
+
+        let resolve = |id: &str| {
+            ids.get(id)
+                .copied()
+                .ok_or_else(|| DefinitionError::UnknownNode(id.to_string()))
+        };
+
+        for (fan_out_id, join) in fan_outs {
+            let join_id = resolve(join)?;
+
+            if let Some(IdentifiedNode {
+                node: Node::FanOut { join },
+                ..
+            }) = graph.nodes.get_mut(&fan_out_id)
+            {
+                *join = join_id;
+            }
+        }
+
+        graph.add_defined_edges(&definition.edges, resolve)?;
+
+        Ok(graph)
+    }
+
+    /// Adds the edges of a definition, once every node it refers to exists.
+    fn add_defined_edges(
+        &mut self,
+        edges: &[EdgeDefinition],
+        resolve: impl Fn(&str) -> Result<NodeId, DefinitionError>,
+    ) -> Result<(), DefinitionError> {
+        // the true edge of a branch has to come first, see `Graph::branch_edges`
+        let mut pending_false = HashMap::new();
+        let mut true_taken = Vec::new();
+        let mut keys = HashSet::new();
+
+        for edge in edges {
+            let from = resolve(&edge.from)?;
+            let to = resolve(&edge.to)?;
+
+            if let Some(key) = &edge.key
+                && matches!(self.node(from), Node::Branch(_) | Node::Router(_))
+                && !keys.insert((from, key.as_str()))
+            {
+                return Err(DefinitionError::DuplicateEdgeKey {
+                    from: edge.from.clone(),
+                    key: key.clone(),
+                });
+            }
+
+            let invalid_key = || DefinitionError::InvalidEdgeKey {
+                from: edge.from.clone(),
+                key: edge.key.clone(),
+            };
+
+            let key = match (self.node(from), edge.key.as_deref()) {
+                (Node::Branch(_), Some("true")) => {
+                    self.edges.push(Edge {
+                        from,
+                        to,
+                        key: None,
+                    });
+                    self.edges.extend(pending_false.remove(&from));
+                    true_taken.push(from);
+                    continue;
+                }
+                (Node::Branch(_), Some("false")) if !true_taken.contains(&from) => {
+                    pending_false.insert(
+                        from,
+                        Edge {
+                            from,
+                            to,
+                            key: None,
+                        },
+                    );
+                    continue;
+                }
+                (Node::Branch(_), Some("false")) => None,
+                (Node::Branch(_), _) | (Node::Router(_), None) => return Err(invalid_key()),
+                (Node::Router(_), Some(key)) => Some(key.to_string()),
+                (_, None) => None,
+                (_, Some(_)) => return Err(invalid_key()),
+            };
+
+            self.edges.push(Edge { from, to, key });
+        }
+
+        self.edges.extend(pending_false.into_values());
+
+        Ok(())
+    }
+
+    /// Describes the graph, naming nodes by their display names and ids by `n` and the node id.
+    pub fn to_definition(&self) -> GraphDefinition {
+        let id = |node_id: NodeId| format!("n{}", node_id.0);
+
+        let nodes = self
+            .sorted_node_ids()
+            .into_iter()
+            .map(|node_id| {
+                let name = self.node(node_id).display_name().to_string();
+
+                let kind = match self.node(node_id) {
+                    _ if node_id == self.start_id => NodeKind::Start,
+                    Node::Action(_) => NodeKind::Action { action: name },
+                    Node::Branch(_) => NodeKind::Branch { condition: name },
+                    Node::Router(_) => NodeKind::Router { router: name },
+                    Node::FanOut { join } => NodeKind::FanOut { join: id(*join) },
+                    Node::Join(_) => NodeKind::Join { merge: name },
+                    Node::Interrupt(_) => NodeKind::Interrupt { name },
+                    Node::Subgraph(_) => NodeKind::Subgraph { subgraph: name },
+                    Node::Terminal => NodeKind::Terminal,
+                };
+
+                NodeDefinition {
+                    id: id(node_id),
+                    kind,
+                }
+            })
+            .collect();
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let key = self
+                    .branch_outcome(edge)
+                    .map_or_else(|| edge.key.clone(), |taken| Some(taken.to_string()));
+
+                EdgeDefinition {
+                    from: id(edge.from),
+                    to: id(edge.to),
+                    key,
+                }
+            })
+            .collect();
+
+        GraphDefinition { nodes, edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{adder, example_graph, multiplier, sum};
+    use crate::{GraphRunner, RunContext, RunOutcome};
+
+    fn example_registry() -> Registry<i32> {
+        Registry::new()
+            .with_action("adder", Box::new(|| adder(1)))
+            .with_action("multiplier", Box::new(|| multiplier(2)))
+            .with_condition(
+                "is \"small\"",
+                Box::new(|| Condition::new("is \"small\"", Box::new(|&x| x < 10))),
+            )
+            .with_router(
+                "parity",
+                Box::new(|| Router::new("parity", Box::new(|x| format!("{}", x % 2)))),
+            )
+            .with_join("sum", Box::new(sum))
+    }
+
+    #[test]
+    fn definition_round_trips() {
+        let definition = example_graph().to_definition();
+
+        let graph = Graph::from_definition(&definition, &example_registry()).unwrap();
+        assert_eq!(graph.to_definition(), definition);
+
+        let loaded = GraphRunner::new(graph).unwrap();
+        let original = GraphRunner::new(example_graph()).unwrap();
+
+        for input in [0, 9, 10, 11] {
+            assert_eq!(
+                loaded.run(input, &RunContext::new()),
+                original.run(input, &RunContext::new())
+            );
+        }
+    }
+
+    #[test]
+    fn definition_loads_from_yaml() {
+        let yaml = r#"
+nodes:
+  - { id: start, kind: start }
+  - { id: grow, kind: action, action: adder }
+  - { id: small, kind: branch, condition: is "small" }
+  - { id: double, kind: action, action: multiplier }
+  - { id: done, kind: terminal }
+edges:
+  - { from: start, to: grow }
+  - { from: grow, to: small }
+  - { from: small, to: double, key: "false" }
+  - { from: small, to: grow, key: "true" }
+  - { from: double, to: done }
+"#;
+
+        let definition: GraphDefinition = serde_yaml::from_str(yaml).unwrap();
+        let graph = Graph::from_definition(&definition, &example_registry()).unwrap();
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(5, &RunContext::new()), RunOutcome::Completed(20));
+    }
+
+    #[test]
+    fn definition_reports_unregistered_names() {
+        let definition = GraphDefinition {
+            nodes: vec![NodeDefinition {
+                id: "a".to_string(),
+                kind: NodeKind::Action {
+                    action: "divider".to_string(),
+                },
+            }],
+            edges: vec![],
+        };
+
+        let err = Graph::from_definition(&definition, &example_registry()).unwrap_err();
+
+        assert_eq!(
+            err,
+            DefinitionError::Unregistered {
+                kind: "action",
+                name: "divider".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn definition_rejects_unkeyed_branch_edges() {
+        let definition = GraphDefinition {
+            nodes: vec![
+                NodeDefinition {
+                    id: "start".to_string(),
+                    kind: NodeKind::Start,
+                },
+                NodeDefinition {
+                    id: "small".to_string(),
+                    kind: NodeKind::Branch {
+                        condition: "is \"small\"".to_string(),
+                    },
+                },
+            ],
+            edges: vec![
+                EdgeDefinition {
+                    from: "start".to_string(),
+                    to: "small".to_string(),
+                    key: None,
+                },
+                EdgeDefinition {
+                    from: "small".to_string(),
+                    to: "start".to_string(),
+                    key: None,
+                },
+            ],
+        };
+
+        let err = Graph::from_definition(&definition, &example_registry()).unwrap_err();
+
+        assert!(matches!(err, DefinitionError::InvalidEdgeKey { .. }));
+    }
+
+    #[test]
+    fn definition_rejects_duplicate_edge_keys() {
+        let load = |edges: &str| {
+            let yaml = format!(
+                r#"
+nodes:
+  - {{ id: start, kind: start }}
+  - {{ id: small, kind: branch, condition: is "small" }}
+  - {{ id: parity, kind: router, router: parity }}
+  - {{ id: done, kind: terminal }}
+edges:
+  - {{ from: start, to: small }}
+  - {{ from: small, to: parity, key: "true" }}
+{edges}
+"#
+            );
+
+            let definition: GraphDefinition = serde_yaml::from_str(&yaml).unwrap();
+            Graph::from_definition(&definition, &example_registry()).map(|_| ())
+        };
+
+        assert_eq!(
+            load(
+                r#"
+  - { from: small, to: done, key: "false" }
+  - { from: small, to: parity, key: "false" }
+  - { from: parity, to: done, key: "0" }"#
+            ),
+            Err(DefinitionError::DuplicateEdgeKey {
+                from: "small".to_string(),
+                key: "false".to_string()
+            })
+        );
+
+        assert_eq!(
+            load(
+                r#"
+  - { from: small, to: done, key: "false" }
+  - { from: parity, to: done, key: "0" }
+  - { from: parity, to: small, key: "0" }"#
+            ),
+            Err(DefinitionError::DuplicateEdgeKey {
+                from: "parity".to_string(),
+                key: "0".to_string()
+            })
+        );
+    }
+}
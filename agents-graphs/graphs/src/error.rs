@@ -0,0 +1,256 @@
+use std::{error::Error, fmt::Display, time::Duration};
+
+use crate::{Limit, NodeId};
+
+/// The error type produced by fallible actions and conditions.
+pub type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+#[derive(Debug)]
+pub enum RunErrorKind {
+    /// A fallible action returned an error.
+    Action(BoxError),
+    /// A fallible condition returned an error.
+    Condition(BoxError),
+    /// The node does not have the outgoing edges it needs to continue.
+    MissingEdge,
+    /// A join node was reached without going through its fan-out.
+    UnmatchedJoin,
+    /// A router returned a key that has no outgoing edge.
+    NoRoute(String),
+    /// An action jumped to a target it did not declare, or that no single node matches.
+    InvalidGoto(String),
+    /// An attempt took longer than the node policy's timeout.
+    TimedOut(Duration),
+    /// The run was cancelled through the runner's `CancellationToken`.
+    Cancelled,
+    /// The run went over one of the runner's limits before this node.
+    LimitExceeded(Limit),
+    /// The checkpoint taken before this node could not be saved.
+    Checkpoint(BoxError),
+    /// The named precondition did not hold for the state the action was given.
+    PreconditionFailed(String),
+    /// The named postcondition did not hold for the state the action produced.
+    PostconditionFailed(String),
+    /// The graph embedded by a subgraph node failed.
+    Subgraph(Box<SubgraphError>),
+    /// An interrupt was reached where the run cannot stop, inside a subgraph or a fan-out path.
+    Interrupted,
+    /// A run was resumed at a node that is not an interrupt.
+    NotAnInterrupt,
+}
+
+impl RunErrorKind {
+    /// Whether the node itself failed, as opposed to the graph or the run, so trying again may help.
+    ///
+    /// A subgraph is retryable when a node of the inner graph failed this way.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Action(_)
+            | Self::Condition(_)
+            | Self::PostconditionFailed(_)
+            | Self::TimedOut(_) => true,
+            Self::Subgraph(e) => e.kind.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+impl Display for RunErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Action(e) => write!(f, "action failed: {e}"),
+            Self::Condition(e) => write!(f, "condition failed: {e}"),
+            Self::MissingEdge => write!(f, "missing outgoing edge"),
+            Self::UnmatchedJoin => write!(f, "join reached outside of its fan-out"),
+            Self::NoRoute(key) => write!(f, "no route for key {key}"),
+            Self::InvalidGoto(target) => write!(f, "cannot jump to {target}"),
+            Self::TimedOut(timeout) => write!(f, "timed out after {timeout:?}"),
+            Self::Cancelled => write!(f, "run cancelled"),
+            Self::LimitExceeded(limit) => write!(f, "run {limit}"),
+            Self::Checkpoint(e) => write!(f, "saving checkpoint failed: {e}"),
+            Self::PreconditionFailed(check) => write!(f, "precondition {check} does not hold"),
+            Self::PostconditionFailed(check) => write!(f, "postcondition {check} does not hold"),
+            Self::Subgraph(e) => write!(f, "subgraph failed at {e}"),
+            Self::Interrupted => write!(f, "interrupted where the run cannot stop"),
+            Self::NotAnInterrupt => write!(f, "cannot resume at a node that is not an interrupt"),
+        }
+    }
+}
+
+/// An error raised while running a graph.
+///
+/// Carries the node where execution stopped and the state as it was
+/// before that node was invoked, so callers can recover or report it.
+#[derive(Debug)]
+pub struct RunError<T> {
+    node_id: NodeId,
+    display_name: String,
+    state: T,
+    kind: RunErrorKind,
+}
+
+impl<T> RunError<T> {
+    pub(crate) fn new(
+        node_id: NodeId,
+        display_name: impl Into<String>,
+        state: T,
+        kind: RunErrorKind,
+    ) -> Self {
+        Self {
+            node_id,
+            display_name: display_name.into(),
+            state,
+            kind,
+        }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn kind(&self) -> &RunErrorKind {
+        &self.kind
+    }
+
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    pub fn into_state(self) -> T {
+        self.state
+    }
+}
+
+impl<T> Display for RunError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "node {:?} ({}): {}",
+            self.node_id, self.display_name, self.kind
+        )
+    }
+}
+
+impl Error for RunErrorKind {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Action(e) | Self::Condition(e) | Self::Checkpoint(e) => Some(e.as_ref()),
+            Self::Subgraph(e) => Some(e.as_ref()),
+            Self::MissingEdge
+            | Self::UnmatchedJoin
+            | Self::NoRoute(_)
+            | Self::InvalidGoto(_)
+            | Self::PreconditionFailed(_)
+            | Self::PostconditionFailed(_)
+            | Self::TimedOut(_)
+            | Self::Cancelled
+            | Self::LimitExceeded(_)
+            | Self::Interrupted
+            | Self::NotAnInterrupt => None,
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> Error for RunError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.kind.source()
+    }
+}
+
+/// Where and why the run of a subgraph's inner graph stopped.
+///
+/// Unlike `RunError`, it does not hold the inner state, which may be of another type;
+/// the outer `RunError` holds the state the subgraph node was given.
+#[derive(Debug)]
+pub struct SubgraphError {
+    node_id: NodeId,
+    display_name: String,
+    kind: RunErrorKind,
+}
+
+impl SubgraphError {
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn kind(&self) -> &RunErrorKind {
+        &self.kind
+    }
+}
+
+impl<S> From<RunError<S>> for SubgraphError {
+    fn from(e: RunError<S>) -> Self {
+        Self {
+            node_id: e.node_id,
+            display_name: e.display_name,
+            kind: e.kind,
+        }
+    }
+}
+
+impl Display for SubgraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "node {:?} ({}): {}",
+            self.node_id, self.display_name, self.kind
+        )
+    }
+}
+
+impl Error for SubgraphError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.kind.source()
+    }
+}
+
+/// An error raised while resuming a run from its last checkpoint, or replaying a trace.
+#[derive(Debug)]
+pub enum ResumeError<T> {
+    /// No checkpoint has been saved for the run id, or none that matches the one to fork from.
+    NoCheckpoint(String),
+    /// The checkpoint store failed to load or save a checkpoint.
+    Store(BoxError),
+    /// The checkpoint or trace names a node that is not part of the runner's graph.
+    UnknownNode(NodeId),
+    /// A fork was asked to continue under a run id that already has checkpoints.
+    RunExists(String),
+    /// The resumed run failed.
+    Run(RunError<T>),
+}
+
+impl<T> From<RunError<T>> for ResumeError<T> {
+    fn from(e: RunError<T>) -> Self {
+        Self::Run(e)
+    }
+}
+
+impl<T> Display for ResumeError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoCheckpoint(run_id) => write!(f, "no checkpoint for run {run_id}"),
+            Self::Store(e) => write!(f, "checkpoint store failed: {e}"),
+            Self::RunExists(run_id) => write!(f, "run {run_id} already has checkpoints"),
+            Self::UnknownNode(node_id) => write!(f, "run continues at unknown node {node_id:?}"),
+            Self::Run(e) => write!(f, "resumed run failed at {e}"),
+        }
+    }
+}
+
+impl<T: std::fmt::Debug> Error for ResumeError<T> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Store(e) => Some(e.as_ref()),
+            Self::Run(e) => e.source(),
+            Self::NoCheckpoint(_) | Self::UnknownNode(_) | Self::RunExists(_) => None,
+        }
+    }
+}
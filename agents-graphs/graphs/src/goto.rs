@@ -0,0 +1,136 @@
+use std::fmt::Display;
+
+use crate::{Action, ActionFn, BoxError, NodeId};
+
+/// Where an action jumps to instead of following its edge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Goto {
+    Node(NodeId),
+    /// The node with this display name, which must be the only one with it.
+    Named(String),
+}
+
+impl From<NodeId> for Goto {
+    fn from(node_id: NodeId) -> Self {
+        Self::Node(node_id)
+    }
+}
+
+impl From<&str> for Goto {
+    fn from(name: &str) -> Self {
+        Self::Named(name.to_string())
+    }
+}
+
+impl From<String> for Goto {
+    fn from(name: String) -> Self {
+        Self::Named(name)
+    }
+}
+
+impl Display for Goto {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Node(node_id) => write!(f, "{node_id:?}"),
+            Self::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The new state, and optionally where to go with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command<T> {
+    pub state: T,
+    pub goto: Option<Goto>,
+}
+
+impl<T> Command<T> {
+    /// Follows the action's edge, like a plain action.
+    pub const fn next(state: T) -> Self {
+        Self { state, goto: None }
+    }
+
+    pub fn goto(state: T, target: impl Into<Goto>) -> Self {
+        Self {
+            state,
+            goto: Some(target.into()),
+        }
+    }
+}
+
+pub type CommandFn<T> = Box<dyn Fn(T) -> Result<Command<T>, BoxError> + Send + Sync>;
+
+impl<T: 'static> Action<T> {
+    /// Creates an action that can jump to one of `targets` instead of following its edge,
+    /// e.g. an agent handing the conversation off to another one.
+    ///
+    /// The targets are checked by `Graph::validate`, and jumping anywhere else fails the run.
+    pub fn new_goto(
+        display_name: impl Into<String>,
+        targets: Vec<Goto>,
+        command: Box<dyn Fn(T) -> Command<T> + Send + Sync>,
+    ) -> Self {
+        Self::new_goto_fallible(
+            display_name,
+            targets,
+            Box::new(move |state| Ok(command(state))),
+        )
+    }
+
+    /// Like `Action::new_goto`, for actions that can fail.
+    pub fn new_goto_fallible(
+        display_name: impl Into<String>,
+        targets: Vec<Goto>,
+        command: CommandFn<T>,
+    ) -> Self {
+        let mut action = Self::with_fn(display_name, ActionFn::Command(command));
+        action.goto_targets = targets;
+        action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{handoff_graph, triage};
+    use crate::{GraphRunner, RunContext, RunErrorKind, RunOutcome};
+
+    #[test]
+    fn actions_jump_to_goto_targets() {
+        let runner = GraphRunner::new(handoff_graph(triage())).unwrap();
+
+        assert_eq!(runner.run(2, &RunContext::new()), RunOutcome::Completed(4));
+        assert_eq!(runner.run(3, &RunContext::new()), RunOutcome::Completed(4));
+    }
+
+    #[test]
+    fn goto_targets_match_by_node() {
+        let double = NodeId(4);
+        let triage = Action::new_goto(
+            "triage",
+            vec!["double".into()],
+            Box::new(move |x| Command::goto(x, double)),
+        );
+
+        let graph = handoff_graph(triage);
+        assert_eq!(graph.node(double).display_name(), "double");
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(3, &RunContext::new()), RunOutcome::Completed(6));
+    }
+
+    #[test]
+    fn undeclared_goto_fails_the_run() {
+        let triage = Action::new_goto(
+            "triage",
+            vec!["double".into()],
+            Box::new(|x| Command::goto(x, "adder")),
+        );
+
+        let runner = GraphRunner::new(handoff_graph(triage)).unwrap();
+        let err = runner.try_run(2, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::InvalidGoto(target) if target == "adder"));
+    }
+}
@@ -1,39 +1,179 @@
-use std::{collections::HashMap, fmt::Debug};
-
-use log::{debug, info};
-
-// The NodeId can only be created internally by the graph structure,
-// so it should be impossible to ever have a NodeId handle that cannot be resolved to its Node,
-// as long as the graph structure does its job. (Nodes are never removed, only created)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+mod analysis;
+mod cancel;
+mod checkpoint;
+mod context;
+mod definition;
+mod error;
+mod goto;
+mod limits;
+mod metrics;
+mod mutation;
+mod observer;
+mod policy;
+mod reduce;
+mod render;
+mod stream;
+#[cfg(test)]
+mod test_support;
+mod trace;
+mod validation;
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    pin::{Pin, pin},
+    time::{Duration, Instant},
+};
+
+use futures::{channel::mpsc::UnboundedSender, future::Either};
+use futures_timer::Delay;
+
+use limits::RunTracker;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use trace::Replay;
+
+pub use analysis::Cycle;
+pub use cancel::{CancellationToken, Cancelled};
+pub use checkpoint::{Checkpoint, CheckpointStore, JsonCheckpointStore, MemoryCheckpointStore};
+pub use context::RunContext;
+pub use definition::{
+    DefinitionError, EdgeDefinition, GraphDefinition, NodeDefinition, NodeKind, Registry,
+};
+pub use error::{BoxError, ResumeError, RunError, RunErrorKind, SubgraphError};
+pub use goto::{Command, CommandFn, Goto};
+pub use limits::{Limit, RunLimits};
+pub use metrics::{Histogram, MetricsRecorder, MetricsSnapshot, NodeMetrics};
+pub use mutation::MutationError;
+pub use observer::{Observer, RunKey};
+pub use policy::NodePolicy;
+use reduce::{KeptUpdate, ReduceFn, UpdateActionFn, UpdateLog};
+pub use reduce::{Reduce, append, overwrite};
+pub use stream::RunEvent;
+pub use trace::{Trace, TraceOutcome, TraceRecorder, TraceStep};
+pub use validation::{IssueKind, ValidationError, ValidationIssue};
+
+// NodeIds are handed out by the graph they belong to, but can also be deserialized,
+// e.g. from a checkpoint or trace, or taken from another graph, so an id is not guaranteed
+// to resolve to a Node; it also stops resolving once its node has been removed.
+// Ids are never handed out twice, and `validate` reports edges to nodes that do not exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(usize);
 
+/// The boxed future returned by async actions and conditions.
+pub type NodeFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type FallibleActionFn<T> = Box<dyn Fn(T) -> Result<T, BoxError> + Send + Sync>;
+type ContextualActionFn<T> = Box<dyn Fn(T, &RunContext) -> Result<T, BoxError> + Send + Sync>;
+type AsyncActionFn<T> = Box<dyn Fn(T) -> NodeFuture<'static, Result<T, BoxError>> + Send + Sync>;
+type AsyncContextualActionFn<T> =
+    Box<dyn for<'c> Fn(T, &'c RunContext) -> NodeFuture<'c, Result<T, BoxError>> + Send + Sync>;
+
+enum ActionFn<T> {
+    Infallible(Box<dyn Fn(T) -> T + Send + Sync>),
+    Fallible(FallibleActionFn<T>),
+    Async(AsyncActionFn<T>),
+    Contextual(ContextualActionFn<T>),
+    AsyncContextual(AsyncContextualActionFn<T>),
+    Command(CommandFn<T>),
+    Update(UpdateActionFn<T>),
+}
+
 pub struct Action<T> {
-    // TODO: add concept of 'preconditions' which are verified before the action is invoked
-    // i.e. 'the last message of the state must have role user'
-    action: Box<dyn Fn(T) -> T>,
+    invoke: ActionFn<T>,
     display_name: String,
+    preconditions: Vec<Condition<T>>,
+    postconditions: Vec<Condition<T>>,
+    goto_targets: Vec<Goto>,
 }
 
 impl<T> Debug for Action<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Action")
             .field("display_name", &self.display_name)
-            .finish()
+            .finish_non_exhaustive()
     }
 }
 
 impl<T> Action<T> {
-    pub fn new(display_name: impl Into<String>, action: Box<dyn Fn(T) -> T>) -> Self {
+    pub fn new(display_name: impl Into<String>, action: Box<dyn Fn(T) -> T + Send + Sync>) -> Self {
+        Self::with_fn(display_name, ActionFn::Infallible(action))
+    }
+
+    /// Creates an action whose failures are reported by `GraphRunner::try_run`
+    /// instead of panicking.
+    pub fn new_fallible(display_name: impl Into<String>, action: FallibleActionFn<T>) -> Self {
+        Self::with_fn(display_name, ActionFn::Fallible(action))
+    }
+
+    /// Creates an action that is awaited by the runner. Failures are reported
+    /// the same way as for fallible actions.
+    pub fn new_async(display_name: impl Into<String>, action: AsyncActionFn<T>) -> Self {
+        Self::with_fn(display_name, ActionFn::Async(action))
+    }
+
+    /// Creates an action that also reads the context of the run it is part of,
+    /// e.g. the API key to call a model with.
+    pub fn new_contextual(display_name: impl Into<String>, action: ContextualActionFn<T>) -> Self {
+        Self::with_fn(display_name, ActionFn::Contextual(action))
+    }
+
+    /// Like `Action::new_contextual`, for actions that are awaited.
+    ///
+    /// The returned future may borrow the context, e.g. to hold on to the API key while calling a model.
+    pub fn new_async_contextual(
+        display_name: impl Into<String>,
+        action: AsyncContextualActionFn<T>,
+    ) -> Self {
+        Self::with_fn(display_name, ActionFn::AsyncContextual(action))
+    }
+
+    fn with_fn(display_name: impl Into<String>, action: ActionFn<T>) -> Self {
         Self {
-            action,
+            invoke: action,
             display_name: display_name.into(),
+            preconditions: Vec::new(),
+            postconditions: Vec::new(),
+            goto_targets: Vec::new(),
         }
     }
+
+    /// Adds a check that must hold for the state before the action is invoked,
+    /// e.g. "the last message of the state must have role user".
+    ///
+    /// Checks run in the order they were added, and the run fails at the first one that does not hold.
+    pub fn with_precondition(mut self, check: Condition<T>) -> Self {
+        self.preconditions.push(check);
+        self
+    }
+
+    /// Adds a check that must hold for the state the action produced.
+    ///
+    /// A violation is retried by the node's policy, since the action may do better next time.
+    pub fn with_postcondition(mut self, check: Condition<T>) -> Self {
+        self.postconditions.push(check);
+        self
+    }
+}
+
+type FallibleConditionFn<T> = Box<dyn Fn(&T) -> Result<bool, BoxError> + Send + Sync>;
+type ContextualConditionFn<T> =
+    Box<dyn Fn(&T, &RunContext) -> Result<bool, BoxError> + Send + Sync>;
+type AsyncConditionFn<T> =
+    Box<dyn Fn(&T) -> NodeFuture<'static, Result<bool, BoxError>> + Send + Sync>;
+type AsyncContextualConditionFn<T> =
+    Box<dyn for<'c> Fn(&T, &'c RunContext) -> NodeFuture<'c, Result<bool, BoxError>> + Send + Sync>;
+
+enum ConditionFn<T> {
+    Infallible(Box<dyn Fn(&T) -> bool + Send + Sync>),
+    Fallible(FallibleConditionFn<T>),
+    Async(AsyncConditionFn<T>),
+    Contextual(ContextualConditionFn<T>),
+    AsyncContextual(AsyncContextualConditionFn<T>),
 }
 
 pub struct Condition<T> {
-    condition: Box<dyn Fn(&T) -> bool>,
+    condition: ConditionFn<T>,
     display_name: String,
 }
 
@@ -41,14 +181,313 @@ impl<T> Debug for Condition<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Condition")
             .field("display_name", &self.display_name)
-            .finish()
+            .finish_non_exhaustive()
     }
 }
 
 impl<T> Condition<T> {
-    pub fn new(display_name: impl Into<String>, condition: Box<dyn Fn(&T) -> bool>) -> Self {
+    pub fn new(
+        display_name: impl Into<String>,
+        condition: Box<dyn Fn(&T) -> bool + Send + Sync>,
+    ) -> Self {
+        Self {
+            condition: ConditionFn::Infallible(condition),
+            display_name: display_name.into(),
+        }
+    }
+
+    /// Creates a condition whose failures are reported by `GraphRunner::try_run`
+    /// instead of panicking.
+    pub fn new_fallible(
+        display_name: impl Into<String>,
+        condition: FallibleConditionFn<T>,
+    ) -> Self {
+        Self {
+            condition: ConditionFn::Fallible(condition),
+            display_name: display_name.into(),
+        }
+    }
+
+    /// Creates a condition that is awaited by the runner.
+    ///
+    /// The returned future cannot borrow the state, so copy out whatever it needs first.
+    pub fn new_async(display_name: impl Into<String>, condition: AsyncConditionFn<T>) -> Self {
+        Self {
+            condition: ConditionFn::Async(condition),
+            display_name: display_name.into(),
+        }
+    }
+
+    /// Creates a condition that also reads the context of the run it is part of.
+    pub fn new_contextual(
+        display_name: impl Into<String>,
+        condition: ContextualConditionFn<T>,
+    ) -> Self {
+        Self {
+            condition: ConditionFn::Contextual(condition),
+            display_name: display_name.into(),
+        }
+    }
+
+    /// Like `Condition::new_contextual`, for conditions that are awaited.
+    ///
+    /// The returned future may borrow the context, but not the state.
+    pub fn new_async_contextual(
+        display_name: impl Into<String>,
+        condition: AsyncContextualConditionFn<T>,
+    ) -> Self {
+        Self {
+            condition: ConditionFn::AsyncContextual(condition),
+            display_name: display_name.into(),
+        }
+    }
+
+    async fn evaluate(&self, state: &T, context: &RunContext) -> Result<bool, BoxError>
+    where
+        T: Sync,
+    {
+        match &self.condition {
+            ConditionFn::Infallible(condition) => Ok(condition(state)),
+            ConditionFn::Fallible(condition) => condition(state),
+            ConditionFn::Async(condition) => condition(state).await,
+            ConditionFn::Contextual(condition) => condition(state, context),
+            ConditionFn::AsyncContextual(condition) => condition(state, context).await,
+        }
+    }
+}
+
+/// Merges the states produced by the paths of a fan-out back into a single state.
+type MergeFn<T> = Box<dyn Fn(Vec<T>) -> T + Send + Sync>;
+
+enum JoinFn<T> {
+    Merge(MergeFn<T>),
+    Reduce(ReduceFn<T>),
+}
+
+pub struct Join<T> {
+    join: JoinFn<T>,
+    display_name: String,
+}
+
+impl<T> Debug for Join<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Join")
+            .field("display_name", &self.display_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Join<T> {
+    /// The merge function receives one state per path, in the order the paths were added.
+    pub fn new(display_name: impl Into<String>, merge: MergeFn<T>) -> Self {
+        Self::with_fn(display_name, JoinFn::Merge(merge))
+    }
+
+    fn with_fn(display_name: impl Into<String>, join: JoinFn<T>) -> Self {
+        Self {
+            join,
+            display_name: display_name.into(),
+        }
+    }
+}
+
+type ContextualRouteFn<T, K> = Box<dyn Fn(&T, &RunContext) -> K + Send + Sync>;
+
+/// Picks the next node by key, for choices between more than two outcomes.
+pub struct Router<T> {
+    route: ContextualRouteFn<T, String>,
+    display_name: String,
+}
+
+impl<T> Debug for Router<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("display_name", &self.display_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Router<T> {
+    /// The key can be a string, or an enum that converts into one.
+    pub fn new<K>(
+        display_name: impl Into<String>,
+        route: Box<dyn Fn(&T) -> K + Send + Sync>,
+    ) -> Self
+    where
+        K: Into<String> + 'static,
+        T: 'static,
+    {
+        Self {
+            route: Box::new(move |state, _| route(state).into()),
+            display_name: display_name.into(),
+        }
+    }
+
+    /// Creates a router that also reads the context of the run it is part of.
+    pub fn new_contextual<K>(
+        display_name: impl Into<String>,
+        route: ContextualRouteFn<T, K>,
+    ) -> Self
+    where
+        K: Into<String> + 'static,
+        T: 'static,
+    {
+        Self {
+            route: Box::new(move |state, context| route(state, context).into()),
+            display_name: display_name.into(),
+        }
+    }
+}
+
+/// Suspends the run so the caller can collect input or approval, then resume it.
+///
+/// The run ends with `RunOutcome::Interrupted`, holding the handle to continue it with
+/// through `GraphRunner::resume_interrupted`. A run cannot stop inside the paths of a fan-out,
+/// so `Graph::validate` rejects interrupts there.
+#[derive(Debug)]
+pub struct Interrupt {
+    display_name: String,
+}
+
+impl Interrupt {
+    pub fn new(display_name: impl Into<String>) -> Self {
+        Self {
+            display_name: display_name.into(),
+        }
+    }
+}
+
+/// How a run ended, when no node failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome<T> {
+    /// The run reached a terminal node, with its final state.
+    Completed(T),
+    /// The run stopped at an interrupt node, waiting for the caller to resume it.
+    Interrupted(Interrupted<T>),
+}
+
+impl<T> RunOutcome<T> {
+    /// The final state, if the run completed.
+    pub fn completed(self) -> Option<T> {
+        match self {
+            Self::Completed(state) => Some(state),
+            Self::Interrupted(_) => None,
+        }
+    }
+
+    /// The state the run completed or stopped with.
+    pub fn state(&self) -> &T {
+        match self {
+            Self::Completed(state) | Self::Interrupted(Interrupted { state, .. }) => state,
+        }
+    }
+}
+
+/// A run suspended at an interrupt node. Serializable, so it can be kept until the caller returns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interrupted<T> {
+    pub node_id: NodeId,
+    /// The state the run resumes with; update it with the caller's input before resuming.
+    pub state: T,
+    /// The id the run saved checkpoints under, which the resumed run keeps saving them under.
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+type MapInFn<T, S> = Box<dyn Fn(&T) -> S + Send + Sync>;
+type MapOutFn<T, S> = Box<dyn Fn(T, S) -> T + Send + Sync>;
+
+/// Runs an inner graph, whose state may be of a different type.
+trait RunSubgraph<T>: Send + Sync {
+    fn run<'a>(
+        &'a self,
+        state: T,
+        context: &'a RunContext,
+    ) -> NodeFuture<'a, Result<T, SubgraphError>>
+    where
+        T: 'a;
+}
+
+struct MappedSubgraph<T, S> {
+    runner: GraphRunner<S>,
+    map_in: MapInFn<T, S>,
+    map_out: MapOutFn<T, S>,
+}
+
+impl<T: Send + Sync, S: Clone + Send + Sync> RunSubgraph<T> for MappedSubgraph<T, S> {
+    fn run<'a>(
+        &'a self,
+        state: T,
+        context: &'a RunContext,
+    ) -> NodeFuture<'a, Result<T, SubgraphError>>
+    where
+        T: 'a,
+    {
+        Box::pin(async move {
+            let inner = (self.map_in)(&state);
+
+            match self.runner.try_run_async(inner, context).await {
+                Ok(RunOutcome::Completed(inner)) => Ok((self.map_out)(state, inner)),
+                Ok(RunOutcome::Interrupted(Interrupted { node_id, state, .. })) => {
+                    Err(RunError::new(
+                        node_id,
+                        self.runner.graph.node(node_id).display_name(),
+                        state,
+                        RunErrorKind::Interrupted,
+                    )
+                    .into())
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+}
+
+/// A complete graph, with its own start and terminals, run as a single node of a parent graph.
+///
+/// The inner graph runs with its own runner, so its nodes are seen by that runner's observers
+/// and count towards that runner's limits, not the parent's. It shares the parent's run context.
+/// Interrupts inside it fail the node with `RunErrorKind::Interrupted`.
+pub struct Subgraph<T> {
+    inner: Box<dyn RunSubgraph<T>>,
+    display_name: String,
+}
+
+impl<T> Debug for Subgraph<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subgraph")
+            .field("display_name", &self.display_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Subgraph<T> {
+    /// Embeds the runner's graph, which has the same state type.
+    pub fn new(display_name: impl Into<String>, runner: GraphRunner<T>) -> Self {
+        Self::mapped(
+            display_name,
+            runner,
+            Box::new(T::clone),
+            Box::new(|_, inner| inner),
+        )
+    }
+
+    /// Embeds a graph over a different state type.
+    ///
+    /// `map_in` builds the inner state from the parent's, and `map_out` folds the inner
+    /// graph's final state back into the parent's.
+    pub fn mapped<S: Clone + Send + Sync + 'static>(
+        display_name: impl Into<String>,
+        runner: GraphRunner<S>,
+        map_in: MapInFn<T, S>,
+        map_out: MapOutFn<T, S>,
+    ) -> Self {
         Self {
-            condition,
+            inner: Box::new(MappedSubgraph {
+                runner,
+                map_in,
+                map_out,
+            }),
             display_name: display_name.into(),
         }
     }
@@ -58,6 +497,14 @@ impl<T> Condition<T> {
 pub enum Node<T> {
     Action(Action<T>),
     Branch(Condition<T>),
+    Router(Router<T>),
+    /// Runs every outgoing path on its own clone of the state until it reaches `join`.
+    FanOut {
+        join: NodeId,
+    },
+    Join(Join<T>),
+    Interrupt(Interrupt),
+    Subgraph(Subgraph<T>),
     Terminal,
 }
 
@@ -66,6 +513,11 @@ impl<T> Node<T> {
         match self {
             Self::Action(action) => &action.display_name,
             Self::Branch(condition) => &condition.display_name,
+            Self::Router(router) => &router.display_name,
+            Self::FanOut { .. } => "FanOut",
+            Self::Join(join) => &join.display_name,
+            Self::Interrupt(interrupt) => &interrupt.display_name,
+            Self::Subgraph(subgraph) => &subgraph.display_name,
             Self::Terminal => "Terminal",
         }
     }
@@ -89,17 +541,36 @@ impl<T> From<Condition<T>> for Node<T> {
     }
 }
 
+impl<T> From<Router<T>> for Node<T> {
+    fn from(router: Router<T>) -> Self {
+        Self::Router(router)
+    }
+}
+
+impl<T> From<Join<T>> for Node<T> {
+    fn from(join: Join<T>) -> Self {
+        Self::Join(join)
+    }
+}
+
+impl<T> From<Interrupt> for Node<T> {
+    fn from(interrupt: Interrupt) -> Self {
+        Self::Interrupt(interrupt)
+    }
+}
+
+impl<T> From<Subgraph<T>> for Node<T> {
+    fn from(subgraph: Subgraph<T>) -> Self {
+        Self::Subgraph(subgraph)
+    }
+}
+
 #[derive(Debug)]
 struct Edge {
     from: NodeId,
     to: NodeId,
-}
-
-#[derive(Clone, Debug)]
-struct BranchEdge {
-    from: NodeId,
-    to_when_true: NodeId,
-    to_when_false: NodeId,
+    /// The router key this edge is taken for, if it leaves a router.
+    key: Option<String>,
 }
 
 #[derive(Debug)]
@@ -124,9 +595,11 @@ impl IdGenerator {
 pub struct Graph<T> {
     nodes: HashMap<NodeId, IdentifiedNode<T>>,
     edges: Vec<Edge>,
-    condition_edges: Vec<BranchEdge>,
     id_generator: IdGenerator,
     start_id: NodeId,
+    start_count: usize,
+    limits_fallback: Option<NodeId>,
+    policies: HashMap<NodeId, NodePolicy>,
 }
 
 fn no_op_start_node<T>() -> Action<T> {
@@ -141,44 +614,61 @@ impl<T> Graph<T> {
         Self {
             nodes: HashMap::new(),
             edges: Vec::new(),
-            condition_edges: Vec::new(),
             id_generator,
             start_id,
+            start_count: 0,
+            limits_fallback: None,
+            policies: HashMap::new(),
         }
     }
 
     pub fn start(&mut self) -> GraphAdding<'_, T> {
         let start_id = self.start_id;
 
-        let previous = self.nodes.insert(
-            start_id,
-            IdentifiedNode {
+        // Starting more than once is reported by `validate`.
+        self.start_count += 1;
+        self.nodes
+            .entry(start_id)
+            .or_insert_with(|| IdentifiedNode {
                 id: start_id,
                 node: no_op_start_node().into(),
-            },
-        );
-
-        debug_assert!(previous.is_none());
+            });
 
-        GraphAdding {
-            graph: self,
-            last_added: start_id,
-        }
+        GraphAdding::new(self, start_id)
     }
 
     pub fn start_id(&self) -> NodeId {
         self.start_id
     }
 
+    /// Designates the node a run continues from when it exceeds one of the runner's limits.
+    ///
+    /// The fallback is taken at most once per run; exceeding a limit again after that
+    /// fails the run.
+    pub fn set_limits_fallback(&mut self, node_id: NodeId) {
+        self.limits_fallback = Some(node_id);
+    }
+
+    /// Sets how the runner retries the node when it fails, replacing any earlier policy.
+    pub fn set_policy(&mut self, node_id: NodeId, policy: NodePolicy) {
+        self.policies.insert(node_id, policy);
+    }
+
     pub fn add_node_from(&mut self, from: NodeId, node: impl Into<Node<T>>) -> GraphAdding<'_, T> {
         let node = node.into();
         let next_id = self.register_node(node);
-        self.edges.push(Edge { from, to: next_id });
+        self.edges.push(Edge {
+            from,
+            to: next_id,
+            key: None,
+        });
 
-        GraphAdding {
-            graph: self,
-            last_added: next_id,
-        }
+        GraphAdding::new(self, next_id)
+    }
+
+    /// Continues building from a node that has no edge leading to it yet, such as a goto target.
+    pub fn continue_from(&mut self, node_id: NodeId) -> GraphAdding<'_, T> {
+        GraphAdding::new(self, node_id)
     }
 
     pub fn register_node(&mut self, node: impl Into<Node<T>>) -> NodeId {
@@ -200,26 +690,14 @@ impl<T> Graph<T> {
         next_id
     }
 
-    fn add_node(&mut self, node: impl Into<Node<T>>) -> GraphAdding<'_, T> {
-        let node_id = self.register_node(node);
-
-        GraphAdding {
-            graph: self,
-            last_added: node_id,
-        }
-    }
-
-    fn add_edge(&mut self, edge: Edge) {
-        self.edges.push(edge);
-    }
-
-    fn make_terminal(&mut self, node_id: NodeId) {
+    fn make_terminal(&mut self, node_id: NodeId, key: Option<String>) {
         let terminal_node_id = self.register_node(Node::Terminal);
 
         // Add an edge from the node to the terminal node:
         self.edges.push(Edge {
             from: node_id,
             to: terminal_node_id,
+            key,
         });
     }
 
@@ -231,8 +709,99 @@ impl<T> Graph<T> {
         self.edges_from(node_id).map(|edge| edge.to)
     }
 
-    fn node(&self, node_id: &NodeId) -> &Node<T> {
-        &self.nodes.get(node_id).expect("Expected a node").node
+    /// The true and false edges of a branch node.
+    ///
+    /// By convention, a branch has two edges, and the first one is the true branch.
+    fn branch_edges(&self, node_id: NodeId) -> (Option<&Edge>, Option<&Edge>) {
+        let mut edges = self.edges_from(node_id);
+        (edges.next(), edges.next())
+    }
+
+    /// The outcome an edge leaving a branch is taken for, or `None` for edges leaving other nodes.
+    fn branch_outcome(&self, edge: &Edge) -> Option<bool> {
+        match self.nodes.get(&edge.from).map(|n| &n.node) {
+            Some(Node::Branch(_)) => {
+                let (true_edge, _) = self.branch_edges(edge.from);
+                Some(true_edge.is_some_and(|true_edge| std::ptr::eq(true_edge, edge)))
+            }
+            _ => None,
+        }
+    }
+
+    fn sorted_node_ids(&self) -> Vec<NodeId> {
+        let mut node_ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        node_ids.sort();
+        node_ids
+    }
+
+    fn node(&self, node_id: NodeId) -> &Node<T> {
+        let identified = self.nodes.get(&node_id).expect("Expected a node");
+        debug_assert_eq!(identified.id, node_id);
+
+        &identified.node
+    }
+
+    /// Every node a goto target can refer to; a valid target refers to exactly one.
+    fn goto_candidates(&self, target: &Goto) -> Vec<NodeId> {
+        match target {
+            Goto::Node(node_id) if self.nodes.contains_key(node_id) => vec![*node_id],
+            Goto::Node(_) => Vec::new(),
+            Goto::Named(name) => self
+                .sorted_node_ids()
+                .into_iter()
+                .filter(|node_id| self.node(*node_id).display_name() == name)
+                .collect(),
+        }
+    }
+
+    fn resolve(&self, target: &Goto) -> Option<NodeId> {
+        match self.goto_candidates(target).as_slice() {
+            [node_id] => Some(*node_id),
+            _ => None,
+        }
+    }
+
+    /// The goto targets an action node declares that can be resolved, in declaration order.
+    fn goto_edges(&self, node_id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let targets = match self.nodes.get(&node_id).map(|n| &n.node) {
+            Some(Node::Action(action)) => action.goto_targets.as_slice(),
+            _ => &[],
+        };
+
+        targets.iter().filter_map(|target| self.resolve(target))
+    }
+
+    /// Every node a run can continue to from the node, including policy fallbacks and goto targets,
+    /// which are entered without an edge.
+    fn successors(&self, node_id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let fallback = self
+            .policies
+            .get(&node_id)
+            .and_then(|policy| policy.fallback);
+
+        self.next_nodes(node_id)
+            .chain(fallback)
+            .chain(self.goto_edges(node_id))
+    }
+
+    /// The nodes a run can begin at: the start node, and the limits fallback, which is entered without an edge.
+    fn roots(&self) -> impl Iterator<Item = NodeId> {
+        std::iter::once(self.start_id).chain(self.limits_fallback)
+    }
+
+    fn reachable_from(&self, roots: impl IntoIterator<Item = NodeId>) -> HashSet<NodeId> {
+        let mut reachable = roots.into_iter().collect::<HashSet<_>>();
+        let mut queue = reachable.iter().copied().collect::<VecDeque<_>>();
+
+        while let Some(node_id) = queue.pop_front() {
+            for next in self.successors(node_id) {
+                if reachable.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reachable
     }
 }
 
@@ -246,10 +815,14 @@ impl<T> Default for Graph<T> {
 pub struct GraphAdding<'a, T> {
     graph: &'a mut Graph<T>,
     last_added: NodeId,
+    /// The router key for the next edge, when adding the first node of a route.
+    key: Option<String>,
 }
 
 pub enum Addable<T> {
     Action(Action<T>),
+    Interrupt(Interrupt),
+    Subgraph(Subgraph<T>),
     ExistingNodeId(NodeId),
 }
 
@@ -265,144 +838,1320 @@ impl<T> From<Action<T>> for Addable<T> {
     }
 }
 
-impl<T> GraphAdding<'_, T> {
-    pub fn then(self, node: impl Into<Addable<T>>) -> Self {
+impl<T> From<Interrupt> for Addable<T> {
+    fn from(v: Interrupt) -> Self {
+        Self::Interrupt(v)
+    }
+}
+
+impl<T> From<Subgraph<T>> for Addable<T> {
+    fn from(v: Subgraph<T>) -> Self {
+        Self::Subgraph(v)
+    }
+}
+
+impl<'a, T> GraphAdding<'a, T> {
+    fn new(graph: &'a mut Graph<T>, last_added: NodeId) -> Self {
+        Self {
+            graph,
+            last_added,
+            key: None,
+        }
+    }
+
+    fn connect(&mut self, to: NodeId) {
+        self.graph.edges.push(Edge {
+            from: self.last_added,
+            to,
+            key: self.key.take(),
+        });
+    }
+
+    pub fn then(mut self, node: impl Into<Addable<T>>) -> Self {
         let addable = node.into();
 
         let next_node_id = match addable {
             Addable::Action(action) => self.graph.register_node(action),
+            Addable::Interrupt(interrupt) => self.graph.register_node(interrupt),
+            Addable::Subgraph(subgraph) => self.graph.register_node(subgraph),
             Addable::ExistingNodeId(node_id) => node_id,
         };
 
-        let edge = Edge {
-            from: self.last_added,
-            to: next_node_id,
-        };
+        self.connect(next_node_id);
 
-        self.graph.edges.push(edge);
+        Self::new(self.graph, next_node_id)
+    }
 
-        Self {
-            graph: self.graph,
-            last_added: next_node_id,
-        }
+    /// Sets how the runner retries the node added last when it fails.
+    pub fn with_policy(self, policy: NodePolicy) -> Self {
+        self.graph.set_policy(self.last_added, policy);
+        self
     }
 
     pub fn branch(
-        self,
+        mut self,
         condition: Condition<T>,
         branch_when_true: impl FnOnce(GraphAdding<'_, T>),
         branch_when_false: impl FnOnce(GraphAdding<'_, T>),
     ) {
         let condition_node_id = self.graph.register_node(condition);
-        self.graph.edges.push(Edge {
-            from: self.last_added,
-            to: condition_node_id,
-        });
+        self.connect(condition_node_id);
 
         let graph = self.graph;
 
-        branch_when_true(GraphAdding {
-            graph,
-            last_added: condition_node_id,
-        });
+        branch_when_true(GraphAdding::new(graph, condition_node_id));
 
-        branch_when_false(GraphAdding {
-            graph,
-            last_added: condition_node_id,
-        });
+        branch_when_false(GraphAdding::new(graph, condition_node_id));
     }
 
-    pub fn terminate(self) {
-        self.graph.make_terminal(self.last_added);
+    /// Adds a router, whose outgoing paths are chosen by the key it returns.
+    ///
+    /// Add a path for each key with `RouteAdding::on`.
+    pub fn route(mut self, router: Router<T>) -> RouteAdding<'a, T> {
+        let router_id = self.graph.register_node(router);
+        self.connect(router_id);
+
+        RouteAdding {
+            graph: self.graph,
+            router_id,
+        }
     }
-}
 
-pub struct GraphRunner<T> {
-    graph: Graph<T>,
-}
+    /// Starts a set of paths that run concurrently, each on a clone of the state.
+    ///
+    /// Add the paths with `FanOutAdding::path`, then call `FanOutAdding::join`
+    /// to merge their results and continue from the join node.
+    pub fn fan_out(mut self, join: Join<T>) -> FanOutAdding<'a, T> {
+        let join_id = self.graph.register_node(join);
+        let fan_out_id = self.graph.register_node(Node::FanOut { join: join_id });
+        self.connect(fan_out_id);
 
-impl<T> GraphRunner<T> {
-    #[must_use]
-    pub const fn new(graph: Graph<T>) -> Self {
-        Self { graph }
+        FanOutAdding {
+            graph: self.graph,
+            fan_out_id,
+            join_id,
+            path_ends: Vec::new(),
+        }
     }
 
-    pub fn run(&self, input: T) -> T {
-        let mut result = input;
+    pub fn terminate(self) {
+        self.graph.make_terminal(self.last_added, self.key);
+    }
+}
 
-        let mut cur_node_id = self.graph.start_id;
+#[derive(Debug)]
+pub struct RouteAdding<'a, T> {
+    graph: &'a mut Graph<T>,
+    router_id: NodeId,
+}
 
-        loop {
-            let cur_node = self.graph.node(&cur_node_id);
+impl<T> RouteAdding<'_, T> {
+    /// Adds the path taken when the router returns `key`.
+    pub fn on(self, key: impl Into<String>, path: impl FnOnce(GraphAdding<'_, T>)) -> Self {
+        let key = key.into();
+
+        assert!(
+            !self
+                .graph
+                .edges_from(self.router_id)
+                .any(|edge| edge.key.as_ref() == Some(&key)),
+            "Router {:?} already has a route for key {key}",
+            self.router_id
+        );
 
-            info!(
-                "Current node: {cur_node_id:?} name: {}",
-                cur_node.display_name()
-            );
+        path(GraphAdding {
+            graph: self.graph,
+            last_added: self.router_id,
+            key: Some(key),
+        });
 
-            match cur_node {
-                Node::Action(action) => {
-                    result = (action.action)(result);
+        self
+    }
+}
 
-                    let mut next_nodes = self.graph.next_nodes(cur_node_id);
-                    let next_node = next_nodes.next().unwrap_or_else(|| {
-                        panic!("Expected an action node {cur_node_id:?} to have at least one edge")
-                    });
+#[derive(Debug)]
+pub struct FanOutAdding<'a, T> {
+    graph: &'a mut Graph<T>,
+    fan_out_id: NodeId,
+    join_id: NodeId,
+    path_ends: Vec<NodeId>,
+}
 
-                    cur_node_id = next_node;
+impl<'a, T> FanOutAdding<'a, T> {
+    /// Adds a path to the fan-out. The last node added by `path` is connected to the join.
+    pub fn path(mut self, path: impl FnOnce(GraphAdding<'_, T>) -> GraphAdding<'_, T>) -> Self {
+        let path_end = path(GraphAdding::new(self.graph, self.fan_out_id)).last_added;
 
-                    {
-                        let next = next_nodes.next();
-                        debug_assert!(next.is_none());
-                    }
-                }
-                Node::Branch(condition) => {
-                    let mut next_nodes = self.graph.next_nodes(cur_node_id);
+        self.path_ends.push(path_end);
 
-                    // by convention, a branch has two edges, and the first one is the true branch
-                    let true_node_id = next_nodes
-                        .next()
-                        .expect("Expected a branch to have at least one edge");
+        self
+    }
 
-                    let false_node_id = next_nodes
-                        .next()
-                        .expect("Expected a branch to have at least two edges");
+    pub fn join(self) -> GraphAdding<'a, T> {
+        for path_end in self.path_ends {
+            self.graph.edges.push(Edge {
+                from: path_end,
+                to: self.join_id,
+                key: None,
+            });
+        }
 
-                    {
-                        let next = next_nodes.next();
-                        debug_assert!(next.is_none());
+        GraphAdding::new(self.graph, self.join_id)
+    }
+}
+
+pub struct GraphRunner<T> {
+    graph: Graph<T>,
+    observers: Vec<Box<dyn Observer<T>>>,
+    limits: RunLimits,
+    checkpoints: Option<Box<dyn CheckpointStore<T>>>,
+}
+
+/// What a single run carries along with it.
+///
+/// Everything but `updates` is shared by all of the run's paths.
+struct RunScope<'a, T> {
+    tracker: &'a RunTracker,
+    /// Set when the run saves checkpoints.
+    run_id: Option<&'a str>,
+    /// Set when the run replays a trace.
+    replay: Option<&'a Replay<T>>,
+    /// Set when the run is streamed.
+    events: Option<&'a UnboundedSender<RunEvent<T>>>,
+    context: &'a RunContext,
+    /// Set when the context holds a token that stops the run.
+    cancellation: Option<&'a CancellationToken>,
+    /// Tells observers which run a callback is about.
+    key: RunKey,
+    /// Set inside the paths of a fan-out, which keep what `Action::update` nodes emit.
+    updates: Option<&'a UpdateLog>,
+}
+
+impl<'a, T> RunScope<'a, T> {
+    fn new(tracker: &'a RunTracker, context: &'a RunContext) -> Self {
+        Self {
+            tracker,
+            run_id: None,
+            replay: None,
+            events: None,
+            context,
+            cancellation: context.get(),
+            key: RunKey::next(),
+            updates: None,
+        }
+    }
+}
+
+impl<T: Send + Sync> GraphRunner<T> {
+    /// Creates a runner for the graph, after checking it with `Graph::validate`.
+    pub fn new(graph: Graph<T>) -> Result<Self, ValidationError> {
+        graph.validate()?;
+
+        Ok(Self {
+            graph,
+            observers: Vec::new(),
+            limits: RunLimits::default(),
+            checkpoints: None,
+        })
+    }
+
+    /// Sets the limits every run is held to. Runs are unlimited by default.
+    pub fn with_limits(mut self, limits: RunLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Registers an observer that is notified as nodes execute, in registration order.
+    pub fn with_observer(mut self, observer: Box<dyn Observer<T>>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Sets the store that runs started with `try_run_with_id` save their checkpoints to.
+    pub fn with_checkpoints(mut self, store: Box<dyn CheckpointStore<T>>) -> Self {
+        self.checkpoints = Some(store);
+        self
+    }
+
+    fn notify(&self, notify: impl Fn(&dyn Observer<T>)) {
+        for observer in &self.observers {
+            notify(observer.as_ref());
+        }
+    }
+
+    /// Runs the graph until it completes or stops at an interrupt, panicking if any node fails.
+    ///
+    /// Async nodes are driven to completion on the current thread.
+    pub fn run(&self, input: T, context: &RunContext) -> RunOutcome<T>
+    where
+        T: Clone,
+    {
+        self.try_run(input, context)
+            .unwrap_or_else(|e| panic!("Graph run failed at {e}"))
+    }
+
+    /// Runs the graph until it completes or stops at an interrupt, giving contextual nodes
+    /// the run's context.
+    ///
+    /// On failure, the returned error names the node that failed and holds the state
+    /// as it was before that node was invoked.
+    ///
+    /// A `CancellationToken` in the context stops the run: the token is checked before every
+    /// node, and async nodes are dropped at their next `await` once it is cancelled. The run
+    /// then fails with `RunErrorKind::Cancelled`, holding the state as it was before the node
+    /// that was stopped. Other runs on the runner carry on.
+    pub fn try_run(&self, input: T, context: &RunContext) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.try_run_async(input, context))
+    }
+
+    /// Like `run`, on the caller's executor.
+    pub async fn run_async(&self, input: T, context: &RunContext) -> RunOutcome<T>
+    where
+        T: Clone,
+    {
+        self.try_run_async(input, context)
+            .await
+            .unwrap_or_else(|e| panic!("Graph run failed at {e}"))
+    }
+
+    /// Like `try_run`, on the caller's executor.
+    ///
+    /// Sync and async nodes can be freely mixed; sync nodes are invoked inline.
+    pub async fn try_run_async(
+        &self,
+        input: T,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let tracker = RunTracker::new(self.limits);
+        let scope = RunScope::new(&tracker, context);
+
+        self.run_to_end(self.graph.start_id, input, &scope).await
+    }
+
+    /// Runs the graph like `try_run`, saving a checkpoint under `run_id` after every node
+    /// so the run can later be picked up again with `resume`.
+    ///
+    /// Nothing is saved unless a store has been set with `with_checkpoints`.
+    pub fn try_run_with_id(
+        &self,
+        run_id: &str,
+        input: T,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.try_run_with_id_async(run_id, input, context))
+    }
+
+    /// Like `try_run_with_id`, on the caller's executor.
+    pub async fn try_run_with_id_async(
+        &self,
+        run_id: &str,
+        input: T,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let tracker = RunTracker::new(self.limits);
+        let scope = RunScope {
+            run_id: Some(run_id),
+            ..RunScope::new(&tracker, context)
+        };
+
+        self.run_to_end(self.graph.start_id, input, &scope).await
+    }
+
+    /// Continues a run from the last checkpoint saved under `run_id`.
+    ///
+    /// The node the run stopped at is invoked again, with the state it was given before.
+    /// Resuming a run that already completed runs only its terminal node.
+    /// The context is not part of the checkpoint, so it is given again.
+    pub fn resume(
+        &self,
+        run_id: &str,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.resume_async(run_id, context))
+    }
+
+    /// Like `resume`, on the caller's executor.
+    pub async fn resume_async(
+        &self,
+        run_id: &str,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        let checkpoint = match &self.checkpoints {
+            Some(store) => store.latest(run_id).map_err(ResumeError::Store)?,
+            None => None,
+        }
+        .ok_or_else(|| ResumeError::NoCheckpoint(run_id.to_string()))?;
+
+        if !self.graph.nodes.contains_key(&checkpoint.node_id) {
+            return Err(ResumeError::UnknownNode(checkpoint.node_id));
+        }
+
+        info!(
+            "Resuming run {run_id} at node {:?} after {} steps",
+            checkpoint.node_id, checkpoint.step
+        );
+
+        let tracker = RunTracker::resumed(self.limits, checkpoint.step);
+        let scope = RunScope {
+            run_id: Some(run_id),
+            ..RunScope::new(&tracker, context)
+        };
+
+        Ok(self
+            .run_to_end(checkpoint.node_id, checkpoint.state, &scope)
+            .await?)
+    }
+
+    /// Every checkpoint saved under `run_id`, oldest first, for picking one to `fork` from.
+    pub fn history(&self, run_id: &str) -> Result<Vec<Checkpoint<T>>, ResumeError<T>> {
+        self.checkpoints.as_ref().map_or_else(
+            || Ok(Vec::new()),
+            |store| store.history(run_id).map_err(ResumeError::Store),
+        )
+    }
+
+    /// Starts a new run under `new_run_id` from a checkpoint of an earlier run,
+    /// usually taken from `history` with its state edited, e.g. to see what would have
+    /// happened had the model answered differently at that step.
+    ///
+    /// The new run's history starts with the earlier run's checkpoints up to the forked one,
+    /// and the earlier run's own history is left as it was. The checkpoint is looked up in
+    /// the earlier run's history by its node and step, taking the latest match.
+    pub fn fork(
+        &self,
+        from: Checkpoint<T>,
+        new_run_id: &str,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.fork_async(from, new_run_id, context))
+    }
+
+    /// Like `fork`, on the caller's executor.
+    pub async fn fork_async(
+        &self,
+        from: Checkpoint<T>,
+        new_run_id: &str,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        let Some(store) = &self.checkpoints else {
+            return Err(ResumeError::NoCheckpoint(from.run_id));
+        };
+
+        if store
+            .latest(new_run_id)
+            .map_err(ResumeError::Store)?
+            .is_some()
+        {
+            return Err(ResumeError::RunExists(new_run_id.to_string()));
+        }
+
+        if !self.graph.nodes.contains_key(&from.node_id) {
+            return Err(ResumeError::UnknownNode(from.node_id));
+        }
+
+        let mut shared = store.history(&from.run_id).map_err(ResumeError::Store)?;
+
+        // steps are counted again from zero after the limits fallback, so they do not order
+        // the history; the checkpoint is found by position instead
+        let Some(position) = shared
+            .iter()
+            .rposition(|c| c.node_id == from.node_id && c.step == from.step)
+        else {
+            return Err(ResumeError::NoCheckpoint(from.run_id));
+        };
+        shared.truncate(position);
+
+        let forked = Checkpoint {
+            run_id: new_run_id.to_string(),
+            ..from
+        };
+
+        for checkpoint in shared {
+            let copied = Checkpoint {
+                run_id: new_run_id.to_string(),
+                ..checkpoint
+            };
+
+            store.save(&copied).map_err(ResumeError::Store)?;
+        }
+
+        store.save(&forked).map_err(ResumeError::Store)?;
+
+        info!(
+            "Forking run {new_run_id} at node {:?} after {} steps",
+            forked.node_id, forked.step
+        );
+
+        let tracker = RunTracker::resumed(self.limits, forked.step);
+        let scope = RunScope {
+            run_id: Some(new_run_id),
+            ..RunScope::new(&tracker, context)
+        };
+
+        Ok(self
+            .run_to_end(forked.node_id, forked.state, &scope)
+            .await?)
+    }
+
+    /// Continues a run that stopped at an interrupt node, from the node after it.
+    pub fn resume_interrupted(
+        &self,
+        interrupted: Interrupted<T>,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.resume_interrupted_async(interrupted, context))
+    }
+
+    /// Like `resume_interrupted`, on the caller's executor.
+    pub async fn resume_interrupted_async(
+        &self,
+        interrupted: Interrupted<T>,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let Interrupted {
+            node_id,
+            state,
+            run_id,
+        } = interrupted;
+
+        let Some(IdentifiedNode {
+            node: Node::Interrupt(interrupt),
+            ..
+        }) = self.graph.nodes.get(&node_id)
+        else {
+            let display_name = self
+                .graph
+                .nodes
+                .get(&node_id)
+                .map_or("<unknown>", |node| node.node.display_name());
+
+            return Err(RunError::new(
+                node_id,
+                display_name,
+                state,
+                RunErrorKind::NotAnInterrupt,
+            ));
+        };
+
+        let Some(next_node) = self.graph.next_nodes(node_id).next() else {
+            return Err(RunError::new(
+                node_id,
+                &interrupt.display_name,
+                state,
+                RunErrorKind::MissingEdge,
+            ));
+        };
+
+        info!("Resuming after interrupt {}", interrupt.display_name);
+
+        let tracker = RunTracker::new(self.limits);
+        let scope = RunScope {
+            run_id: run_id.as_deref(),
+            ..RunScope::new(&tracker, context)
+        };
+
+        self.run_to_end(next_node, state, &scope).await
+    }
+
+    /// Re-drives the graph from a recorded trace, from the node and input the trace started with.
+    ///
+    /// Action, subgraph, branch and router nodes with a recorded step are not invoked; they
+    /// produce the state and take the edge that was recorded, in the order recorded for each node.
+    /// Every other node runs live, so a run that failed fails again at the same node,
+    /// without calling anything that completed in the recording.
+    pub fn try_replay(
+        &self,
+        trace: &Trace<T>,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        futures::executor::block_on(self.try_replay_async(trace, context))
+    }
+
+    /// Like `try_replay`, on the caller's executor.
+    pub async fn try_replay_async(
+        &self,
+        trace: &Trace<T>,
+        context: &RunContext,
+    ) -> Result<RunOutcome<T>, ResumeError<T>>
+    where
+        T: Clone,
+    {
+        // the trace may have been recorded against a different version of the graph
+        if !self.graph.nodes.contains_key(&trace.start) {
+            return Err(ResumeError::UnknownNode(trace.start));
+        }
+
+        let tracker = RunTracker::new(self.limits);
+        let replay = Replay::new(trace);
+        let scope = RunScope {
+            replay: Some(&replay),
+            ..RunScope::new(&tracker, context)
+        };
+
+        Ok(self
+            .run_to_end(trace.start, trace.input.clone(), &scope)
+            .await?)
+    }
+
+    /// Runs from `start` to a terminal, taking the limits fallback if needed, and reports the result.
+    async fn run_to_end(
+        &self,
+        start: NodeId,
+        input: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<RunOutcome<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let mut result = self.run_from(start, input, None, scope).await;
+
+        if let Some(fallback) = self.graph.limits_fallback
+            && let Err(e) = result
+        {
+            result = if let RunErrorKind::LimitExceeded(limit) = e.kind() {
+                warn!(
+                    "Run {limit} at node {:?} ({}), continuing from fallback {fallback:?}",
+                    e.node_id(),
+                    e.display_name()
+                );
+
+                scope.tracker.reset();
+                self.run_from(fallback, e.into_state(), None, scope).await
+            } else {
+                Err(e)
+            };
+        }
+
+        self.notify(|observer| observer.on_run_end(scope.key, result.as_ref()));
+
+        result
+    }
+
+    /// Runs from `start` until a terminal or interrupt is reached, or until the next node
+    /// would be `stop_at`.
+    fn run_from<'a>(
+        &'a self,
+        start: NodeId,
+        input: T,
+        stop_at: Option<NodeId>,
+        scope: &'a RunScope<'a, T>,
+    ) -> NodeFuture<'a, Result<RunOutcome<T>, RunError<T>>>
+    where
+        T: Clone,
+    {
+        // Boxed, because fan-out paths are run by recursing back into this function.
+        Box::pin(async move {
+            let mut state = input;
+            let mut cur_node_id = start;
+
+            while Some(cur_node_id) != stop_at {
+                match self.step(cur_node_id, state, scope).await? {
+                    Step::Next(next_node_id, next_state) => {
+                        // fan-out paths are not checkpointed; resuming re-runs the whole fan-out
+                        if stop_at.is_none() {
+                            self.checkpoint(scope, next_node_id, &next_state)?;
+                        }
+
+                        cur_node_id = next_node_id;
+                        state = next_state;
+                    }
+                    Step::Done(final_state) => return Ok(RunOutcome::Completed(final_state)),
+                    Step::Interrupted(state) => {
+                        return Ok(RunOutcome::Interrupted(Interrupted {
+                            node_id: cur_node_id,
+                            state,
+                            run_id: scope.run_id.map(ToString::to_string),
+                        }));
                     }
+                }
+            }
+
+            Ok(RunOutcome::Completed(state))
+        })
+    }
+
+    /// Saves where the run continues from, if the run saves checkpoints.
+    fn checkpoint(
+        &self,
+        scope: &RunScope<'_, T>,
+        node_id: NodeId,
+        state: &T,
+    ) -> Result<(), RunError<T>>
+    where
+        T: Clone,
+    {
+        let (Some(store), Some(run_id)) = (&self.checkpoints, scope.run_id) else {
+            return Ok(());
+        };
 
-                    if (condition.condition)(&result) {
-                        info!("Condition {} evaluated to true", condition.display_name);
-                        cur_node_id = true_node_id;
+        let checkpoint = Checkpoint {
+            run_id: run_id.to_string(),
+            node_id,
+            state: state.clone(),
+            step: scope.tracker.steps(),
+        };
+
+        store.save(&checkpoint).map_err(|e| {
+            RunError::new(
+                node_id,
+                self.graph.node(node_id).display_name(),
+                checkpoint.state,
+                RunErrorKind::Checkpoint(e),
+            )
+        })
+    }
+
+    async fn step(
+        &self,
+        cur_node_id: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let display_name = self.graph.node(cur_node_id).display_name();
+
+        info!("Current node: {cur_node_id:?} name: {display_name}");
+
+        if let Err(limit) = scope.tracker.visit(cur_node_id) {
+            return Err(RunError::new(
+                cur_node_id,
+                display_name,
+                state,
+                RunErrorKind::LimitExceeded(limit),
+            ));
+        }
+
+        if scope
+            .cancellation
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            info!("Run cancelled before node {cur_node_id:?}");
+
+            return Err(RunError::new(
+                cur_node_id,
+                display_name,
+                state,
+                RunErrorKind::Cancelled,
+            ));
+        }
+
+        self.notify(|observer| {
+            observer.on_node_start(scope.key, cur_node_id, display_name, &state);
+        });
+
+        let started = Instant::now();
+        let step = match self.replayed(cur_node_id, &state, scope)? {
+            Some(step) => step,
+            None => self.invoke_cancellable(cur_node_id, state, scope).await?,
+        };
+        let elapsed = started.elapsed();
+
+        self.notify(|observer| {
+            observer.on_node_end(scope.key, cur_node_id, display_name, step.state(), elapsed);
+        });
+
+        if let Some(events) = scope.events {
+            // the receiver is only gone if the caller dropped the stream
+            events
+                .unbounded_send(RunEvent::Node {
+                    node_id: cur_node_id,
+                    display_name: display_name.to_string(),
+                    state: step.state().clone(),
+                })
+                .ok();
+        }
+
+        Ok(step)
+    }
+
+    async fn invoke(
+        &self,
+        cur_node_id: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let cur_node = self.graph.node(cur_node_id);
+
+        let fail = |state: T, kind: RunErrorKind| {
+            RunError::new(cur_node_id, cur_node.display_name(), state, kind)
+        };
+
+        match cur_node {
+            Node::Action(action) => {
+                let mut next_nodes = self.graph.next_nodes(cur_node_id);
+                let Some(next_node) = next_nodes.next() else {
+                    return Err(fail(state, RunErrorKind::MissingEdge));
+                };
+
+                {
+                    let next = next_nodes.next();
+                    debug_assert!(next.is_none());
+                }
+
+                let command = self
+                    .invoke_action(cur_node_id, action, state, scope)
+                    .await?;
+
+                let Some(target) = command.goto else {
+                    return Ok(Step::Next(next_node, command.state));
+                };
+
+                let Some(next_node) = self.resolve_goto(action, &target) else {
+                    return Err(fail(
+                        command.state,
+                        RunErrorKind::InvalidGoto(target.to_string()),
+                    ));
+                };
+
+                info!("Action {} jumped to {target}", action.display_name);
+
+                self.notify(|observer| {
+                    observer.on_goto(scope.key, cur_node_id, &action.display_name, next_node);
+                });
+
+                Ok(Step::Next(next_node, command.state))
+            }
+            Node::Branch(condition) => {
+                let (Some(true_edge), Some(false_edge)) = self.graph.branch_edges(cur_node_id)
+                else {
+                    return Err(fail(state, RunErrorKind::MissingEdge));
+                };
+
+                let taken = match condition.evaluate(&state, scope.context).await {
+                    Ok(taken) => taken,
+                    Err(e) => return Err(fail(state, RunErrorKind::Condition(e))),
+                };
+
+                info!("Condition {} evaluated to {taken}", condition.display_name);
+
+                self.notify(|observer| {
+                    observer.on_branch(scope.key, cur_node_id, &condition.display_name, taken);
+                });
+
+                if taken {
+                    Ok(Step::Next(true_edge.to, state))
+                } else {
+                    Ok(Step::Next(false_edge.to, state))
+                }
+            }
+            Node::Router(router) => {
+                let key = (router.route)(&state, scope.context);
+
+                let next_node = self
+                    .graph
+                    .edges_from(cur_node_id)
+                    .find(|edge| edge.key.as_ref() == Some(&key))
+                    .map(|edge| edge.to);
+
+                info!("Router {} returned key {key}", router.display_name);
+
+                self.notify(|observer| {
+                    observer.on_route(scope.key, cur_node_id, &router.display_name, &key);
+                });
+
+                match next_node {
+                    Some(next_node) => Ok(Step::Next(next_node, state)),
+                    None => Err(fail(state, RunErrorKind::NoRoute(key))),
+                }
+            }
+            Node::FanOut { join } => self.fan_out(cur_node_id, *join, state, scope).await,
+            // Joins are handled by the fan-out they belong to, so reaching one here
+            // means it was entered from outside of its fan-out.
+            Node::Join(_) => Err(fail(state, RunErrorKind::UnmatchedJoin)),
+            Node::Subgraph(subgraph) => {
+                let Some(next_node) = self.graph.next_nodes(cur_node_id).next() else {
+                    return Err(fail(state, RunErrorKind::MissingEdge));
+                };
+
+                let before = state.clone();
+                let state = subgraph
+                    .inner
+                    .run(state, scope.context)
+                    .await
+                    .map_err(|e| fail(before, RunErrorKind::Subgraph(Box::new(e))))?;
+
+                Ok(Step::Next(next_node, state))
+            }
+            Node::Interrupt(interrupt) => {
+                info!("Interrupted at {}", interrupt.display_name);
+
+                Ok(Step::Interrupted(state))
+            }
+            Node::Terminal => Ok(Step::Done(state)),
+        }
+    }
+
+    /// The recorded step of the node, when replaying a trace that has one left.
+    fn replayed(
+        &self,
+        cur_node_id: NodeId,
+        state: &T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Option<Step<T>>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let Some(replay) = scope.replay else {
+            return Ok(None);
+        };
+        let node = self.graph.node(cur_node_id);
+
+        // fan-outs replay through their paths, and the other nodes have nothing to replay
+        if !matches!(
+            node,
+            Node::Action(_) | Node::Subgraph(_) | Node::Branch(_) | Node::Router(_)
+        ) {
+            return Ok(None);
+        }
+
+        let Some(recorded) = replay.next(cur_node_id) else {
+            return Ok(None);
+        };
+
+        // the join needs the update, which the trace does not record
+        if let Node::Action(action) = node
+            && matches!(action.invoke, ActionFn::Update(_))
+            && scope.updates.is_some()
+        {
+            return Ok(None);
+        }
+
+        if let Some(target) = recorded.goto
+            && !self.graph.nodes.contains_key(&target)
+        {
+            return Err(RunError::new(
+                cur_node_id,
+                node.display_name(),
+                state.clone(),
+                RunErrorKind::InvalidGoto(Goto::Node(target).to_string()),
+            ));
+        }
+
+        let next_node = match node {
+            Node::Branch(_) => {
+                let Some(taken) = recorded.branch else {
+                    return Ok(None);
+                };
+                self.notify(|observer| {
+                    observer.on_branch(scope.key, cur_node_id, node.display_name(), taken);
+                });
+
+                let (true_edge, false_edge) = self.graph.branch_edges(cur_node_id);
+                if taken { true_edge } else { false_edge }.map(|edge| edge.to)
+            }
+            Node::Router(_) => {
+                let Some(key) = recorded.route else {
+                    return Ok(None);
+                };
+                self.notify(|observer| {
+                    observer.on_route(scope.key, cur_node_id, node.display_name(), &key);
+                });
+
+                self.graph
+                    .edges_from(cur_node_id)
+                    .find(|edge| edge.key.as_ref() == Some(&key))
+                    .map(|edge| edge.to)
+            }
+            _ => recorded
+                .goto
+                .inspect(|target| {
+                    self.notify(|observer| {
+                        observer.on_goto(scope.key, cur_node_id, node.display_name(), *target);
+                    });
+                })
+                .or_else(|| self.graph.next_nodes(cur_node_id).next()),
+        };
+
+        let Some(next_node) = next_node else {
+            return Ok(None);
+        };
+
+        debug!("Replaying recorded step of node {cur_node_id:?}");
+
+        Ok(Some(Step::Next(next_node, recorded.state)))
+    }
+
+    /// Invokes the node, stopping it if the run is cancelled while it is pending.
+    async fn invoke_cancellable(
+        &self,
+        cur_node_id: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let Some(token) = scope.cancellation else {
+            return self.invoke_with_policy(cur_node_id, state, scope).await;
+        };
+
+        let before = state.clone();
+        let invoke = pin!(self.invoke_with_policy(cur_node_id, state, scope));
+
+        match futures::future::select(invoke, token.cancelled()).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                info!("Run cancelled during node {cur_node_id:?}");
+
+                Err(RunError::new(
+                    cur_node_id,
+                    self.graph.node(cur_node_id).display_name(),
+                    before,
+                    RunErrorKind::Cancelled,
+                ))
+            }
+        }
+    }
+
+    /// Invokes the node, retrying it as its policy allows.
+    async fn invoke_with_policy(
+        &self,
+        cur_node_id: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let Some(policy) = self.graph.policies.get(&cur_node_id) else {
+            let result = self.invoke(cur_node_id, state, scope).await;
+
+            if let Err(e) = &result {
+                self.report_failure(cur_node_id, e, false, scope);
+            }
+
+            return result;
+        };
+
+        let mut attempt = 1;
+        let mut backoff = policy.backoff;
+
+        loop {
+            let result = match policy.timeout {
+                Some(timeout) => {
+                    self.invoke_with_timeout(cur_node_id, state.clone(), scope, timeout)
+                        .await
+                }
+                None => self.invoke(cur_node_id, state.clone(), scope).await,
+            };
+
+            if let Err(e) = &result {
+                let retrying = e.kind().is_retryable() && attempt < policy.max_attempts;
+                self.report_failure(cur_node_id, e, retrying, scope);
+            }
+
+            match result {
+                Err(e) if e.kind().is_retryable() => {
+                    if attempt < policy.max_attempts {
+                        warn!(
+                            "Attempt {attempt} of {} at node {cur_node_id:?} failed: {}, retrying in {backoff:?}",
+                            policy.max_attempts,
+                            e.kind()
+                        );
+
+                        Delay::new(backoff).await;
+                        backoff *= 2;
+                        attempt += 1;
+                    } else if let Some(fallback) = policy.fallback {
+                        warn!(
+                            "Node {cur_node_id:?} failed after {attempt} attempt(s): {}, continuing from fallback {fallback:?}",
+                            e.kind()
+                        );
+
+                        return Ok(Step::Next(fallback, state));
                     } else {
-                        info!("Condition {} evaluated to false", condition.display_name);
-                        cur_node_id = false_node_id;
+                        return Err(e);
                     }
                 }
-                Node::Terminal => return result,
+                result => return result,
             }
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The node the action jumps to, if the target is one it declared.
+    fn resolve_goto(&self, action: &Action<T>, target: &Goto) -> Option<NodeId> {
+        // a target may be declared by name and returned by id, or the other way around
+        self.graph.resolve(target).filter(|next_node| {
+            action
+                .goto_targets
+                .iter()
+                .any(|declared| self.graph.resolve(declared) == Some(*next_node))
+        })
+    }
 
-    fn adder(add: i32) -> Action<i32> {
-        Action::new("adder", Box::new(move |x| x + add))
+    /// Tells observers that an attempt at the node failed.
+    fn report_failure(
+        &self,
+        cur_node_id: NodeId,
+        e: &RunError<T>,
+        retrying: bool,
+        scope: &RunScope<'_, T>,
+    ) {
+        // failures inside a fan-out's paths were reported by the node they happened at
+        if e.node_id() != cur_node_id {
+            return;
+        }
+
+        self.notify(|observer| {
+            observer.on_node_failed(scope.key, cur_node_id, e.display_name(), e.kind(), retrying);
+        });
     }
 
-    fn subtractor(subtract: i32) -> Action<i32> {
-        Action::new("subtractor", Box::new(move |x| x - subtract))
+    async fn invoke_with_timeout(
+        &self,
+        cur_node_id: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+        timeout: Duration,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let before = state.clone();
+        let attempt = pin!(self.invoke(cur_node_id, state, scope));
+
+        match futures::future::select(attempt, Delay::new(timeout)).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(RunError::new(
+                cur_node_id,
+                self.graph.node(cur_node_id).display_name(),
+                before,
+                RunErrorKind::TimedOut(timeout),
+            )),
+        }
     }
 
-    fn multiplier(multiply: i32) -> Action<i32> {
-        Action::new("multiplier", Box::new(move |x| x * multiply))
+    async fn invoke_action(
+        &self,
+        cur_node_id: NodeId,
+        action: &Action<T>,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Command<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let context = scope.context;
+        let fail =
+            |state: T, kind| RunError::new(cur_node_id, action.display_name.clone(), state, kind);
+
+        match failed_check(&action.preconditions, &state, context).await {
+            Ok(None) => {}
+            Ok(Some(check)) => {
+                return Err(fail(state, RunErrorKind::PreconditionFailed(check.into())));
+            }
+            Err(e) => return Err(fail(state, RunErrorKind::Condition(e))),
+        }
+
+        // without postconditions, an infallible action cannot fail, so needs no copy of the state
+        if let ActionFn::Infallible(invoke) = &action.invoke
+            && action.postconditions.is_empty()
+        {
+            return Ok(Command::next(invoke(state)));
+        }
+
+        let before = state.clone();
+        let mut kept = None;
+
+        let result = match &action.invoke {
+            ActionFn::Infallible(invoke) => Ok(Command::next(invoke(state))),
+            ActionFn::Fallible(invoke) => invoke(state).map(Command::next),
+            ActionFn::Async(invoke) => invoke(state).await.map(Command::next),
+            ActionFn::Contextual(invoke) => invoke(state, context).map(Command::next),
+            ActionFn::AsyncContextual(invoke) => invoke(state, context).await.map(Command::next),
+            ActionFn::Command(invoke) => invoke(state),
+            ActionFn::Update(invoke) => {
+                invoke(state, scope.updates.is_some()).map(|(state, update)| {
+                    kept = update;
+                    Command::next(state)
+                })
+            }
+        };
+
+        let after = match result {
+            Ok(after) => after,
+            Err(e) => return Err(fail(before, RunErrorKind::Action(e))),
+        };
+
+        match failed_check(&action.postconditions, &after.state, context).await {
+            Ok(None) => {
+                if let (Some(updates), Some(update)) = (scope.updates, kept) {
+                    updates
+                        .lock()
+                        .expect("update log lock poisoned")
+                        .push(update);
+                }
+                Ok(after)
+            }
+            Ok(Some(check)) => Err(fail(
+                before,
+                RunErrorKind::PostconditionFailed(check.into()),
+            )),
+            Err(e) => Err(fail(before, RunErrorKind::Condition(e))),
+        }
     }
 
+    /// Runs every path of the fan-out at `fan_out_id` until `join`, then merges their states.
+    async fn fan_out(
+        &self,
+        fan_out_id: NodeId,
+        join: NodeId,
+        state: T,
+        scope: &RunScope<'_, T>,
+    ) -> Result<Step<T>, RunError<T>>
+    where
+        T: Clone,
+    {
+        let missing_edge =
+            |state| RunError::new(fan_out_id, "FanOut", state, RunErrorKind::MissingEdge);
+
+        let Node::Join(join_node) = self.graph.node(join) else {
+            return Err(missing_edge(state));
+        };
+
+        let Some(next_node) = self.graph.next_nodes(join).next() else {
+            return Err(missing_edge(state));
+        };
+
+        // paths keep their updates for a reducing join, or to pass them on to an enclosing one
+        let keep = matches!(join_node.join, JoinFn::Reduce(_)) || scope.updates.is_some();
+        let path_starts: Vec<_> = self.graph.next_nodes(fan_out_id).collect();
+        let logs: Vec<UpdateLog> = path_starts.iter().map(|_| UpdateLog::default()).collect();
+        let scopes: Vec<_> = logs
+            .iter()
+            .map(|log| RunScope {
+                updates: keep.then_some(log),
+                ..*scope
+            })
+            .collect();
+
+        let paths = path_starts.iter().zip(&scopes).map(|(&path_start, scope)| {
+            self.run_from(path_start, state.clone(), Some(join), scope)
+        });
+
+        let results = futures::future::try_join_all(paths)
+            .await?
+            .into_iter()
+            .map(|outcome| match outcome {
+                RunOutcome::Completed(state) => Ok(state),
+                // validation rejects these, but a goto can still lead into a path
+                RunOutcome::Interrupted(Interrupted { node_id, state, .. }) => Err(RunError::new(
+                    node_id,
+                    self.graph.node(node_id).display_name(),
+                    state,
+                    RunErrorKind::Interrupted,
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        drop(scopes);
+        let updates: Vec<KeptUpdate> = logs
+            .into_iter()
+            .flat_map(|log| log.into_inner().expect("update log lock poisoned"))
+            .collect();
+
+        info!(
+            "Joining {} paths at {}",
+            results.len(),
+            join_node.display_name
+        );
+
+        let state = match &join_node.join {
+            JoinFn::Merge(merge) => merge(results),
+            JoinFn::Reduce(reduce) => reduce(state, &updates),
+        };
+
+        if let Some(outer) = scope.updates {
+            outer
+                .lock()
+                .expect("update log lock poisoned")
+                .extend(updates);
+        }
+
+        Ok(Step::Next(next_node, state))
+    }
+}
+
+/// The name of the first check that does not hold for the state.
+async fn failed_check<'c, T: Sync>(
+    checks: &'c [Condition<T>],
+    state: &T,
+    context: &RunContext,
+) -> Result<Option<&'c str>, BoxError> {
+    for check in checks {
+        if !check.evaluate(state, context).await? {
+            return Ok(Some(&check.display_name));
+        }
+    }
+
+    Ok(None)
+}
+
+enum Step<T> {
+    Next(NodeId, T),
+    Done(T),
+    Interrupted(T),
+}
+
+impl<T> Step<T> {
+    const fn state(&self) -> &T {
+        match self {
+            Self::Next(_, state) | Self::Done(state) | Self::Interrupted(state) => state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    };
+
+    use super::*;
+    use crate::test_support::{
+        RecordingObserver, Size, adder, counting_loop, example_graph, flaky_graph, is_even,
+        multiplier, size_router, subtractor, sum,
+    };
+
     #[test]
     fn one_plus_one_branching() {
         let mut graph = Graph::new();
@@ -413,19 +2162,638 @@ mod tests {
             .then(adder(2))
             .then(multiplier(3))
             .branch(
-                Condition {
-                    condition: Box::new(|&x| x > 10),
-                    display_name: "is_greater_than_10".to_string(),
-                },
+                Condition::new("is_greater_than_10", Box::new(|&x| x > 10)),
                 |graph| graph.then(adder(2)).terminate(),
                 |graph| graph.then(subtractor(1)).terminate(),
             );
 
-        let runner = GraphRunner::new(graph);
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let result = runner.run(3, &RunContext::new());
+
+        assert_eq!(result, RunOutcome::Completed(20));
+    }
+
+    #[test]
+    fn try_run_reports_failing_node_and_prior_state() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(adder(1))
+            .then(Action::new_fallible(
+                "fails_when_odd",
+                Box::new(|x: i32| {
+                    if x % 2 == 0 {
+                        Ok(x)
+                    } else {
+                        Err(format!("{x} is odd").into())
+                    }
+                }),
+            ))
+            .then(multiplier(3))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(
+            runner.try_run(1, &RunContext::new()).unwrap(),
+            RunOutcome::Completed(6)
+        );
+
+        let err = runner.try_run(2, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "fails_when_odd");
+        assert_eq!(err.node_id(), NodeId(2));
+        assert!(matches!(err.kind(), RunErrorKind::Action(_)));
+        assert_eq!(err.into_state(), 3);
+    }
+
+    #[test]
+    fn try_run_reports_failing_condition() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1)).branch(
+            Condition::new_fallible("always_fails", Box::new(|_| Err("no decision".into()))),
+            |graph| graph.then(adder(1)).terminate(),
+            |graph| graph.then(subtractor(1)).terminate(),
+        );
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "always_fails");
+        assert!(matches!(err.kind(), RunErrorKind::Condition(_)));
+        assert_eq!(*err.state(), 2);
+    }
+
+    fn async_doubler() -> Action<i32> {
+        Action::new_async(
+            "async_doubler",
+            Box::new(|x| Box::pin(async move { Ok(x * 2) })),
+        )
+    }
+
+    #[test]
+    fn run_async_mixes_sync_and_async_nodes() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1)).then(async_doubler()).branch(
+            Condition::new_async(
+                "async_is_greater_than_10",
+                Box::new(|&x| Box::pin(async move { Ok(x > 10) })),
+            ),
+            |graph| graph.then(adder(100)).terminate(),
+            |graph| graph.then(subtractor(100)).terminate(),
+        );
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(
+            futures::executor::block_on(runner.run_async(5, &RunContext::new())),
+            RunOutcome::Completed(112)
+        );
+        assert_eq!(
+            futures::executor::block_on(runner.run_async(1, &RunContext::new())),
+            RunOutcome::Completed(-96)
+        );
+
+        // the sync entry points drive async nodes too
+        assert_eq!(
+            runner.run(5, &RunContext::new()),
+            RunOutcome::Completed(112)
+        );
+    }
+
+    #[test]
+    fn try_run_async_reports_failing_async_action() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(adder(1))
+            .then(Action::new_async(
+                "async_fails",
+                Box::new(|_| Box::pin(async { Err("unavailable".into()) })),
+            ))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err =
+            futures::executor::block_on(runner.try_run_async(1, &RunContext::new())).unwrap_err();
+
+        assert_eq!(err.display_name(), "async_fails");
+        assert_eq!(err.into_state(), 2);
+    }
+
+    #[test]
+    fn fan_out_merges_paths_in_order() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(adder(1))
+            .fan_out(Join::new(
+                "collect",
+                Box::new(|results| results[0] * 100 + results[1] * 10 + results[2]),
+            ))
+            .path(|path| path.then(adder(1)))
+            .path(|path| path.then(multiplier(2)).then(subtractor(1)))
+            .path(|path| path)
+            .join()
+            .then(adder(1000))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // paths see 3, and produce 4, 5 and 3
+        assert_eq!(
+            runner.run(2, &RunContext::new()),
+            RunOutcome::Completed(1453)
+        );
+    }
+
+    #[test]
+    fn fan_out_runs_async_paths_concurrently() {
+        let (sender, receiver) = futures::channel::oneshot::channel::<i32>();
+        let receiver = std::sync::Mutex::new(Some(receiver));
+        let sender = std::sync::Mutex::new(Some(sender));
+
+        let mut graph = Graph::new();
+
+        // the first path can only finish once the second one has run
+        graph
+            .start()
+            .fan_out(sum())
+            .path(|path| {
+                path.then(Action::new_async(
+                    "wait_for_other_path",
+                    Box::new(move |x| {
+                        let receiver = receiver.lock().unwrap().take().unwrap();
+                        Box::pin(async move { Ok(x + receiver.await?) })
+                    }),
+                ))
+            })
+            .path(|path| {
+                path.then(Action::new(
+                    "notify_other_path",
+                    Box::new(move |x| {
+                        sender.lock().unwrap().take().unwrap().send(10).unwrap();
+                        x
+                    }),
+                ))
+            })
+            .join()
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(12));
+    }
+
+    #[test]
+    fn fan_out_reports_failing_path() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .fan_out(sum())
+            .path(|path| path.then(adder(1)))
+            .path(|path| {
+                path.then(adder(2)).then(Action::new_fallible(
+                    "fails",
+                    Box::new(|_| Err("path failed".into())),
+                ))
+            })
+            .join()
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "fails");
+        assert_eq!(err.into_state(), 3);
+    }
+
+    #[test]
+    fn router_follows_edge_for_returned_key() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .route(size_router())
+            .on(Size::Small, |graph| graph.then(adder(1)).terminate())
+            .on(Size::Medium, |graph| graph.then(multiplier(2)).terminate())
+            .on(Size::Large, |graph| {
+                graph.terminate();
+            });
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(5, &RunContext::new()), RunOutcome::Completed(6));
+        assert_eq!(
+            runner.run(50, &RunContext::new()),
+            RunOutcome::Completed(100)
+        );
+        assert_eq!(
+            runner.run(500, &RunContext::new()),
+            RunOutcome::Completed(500)
+        );
+    }
+
+    #[test]
+    fn router_reports_key_without_edge() {
+        let mut graph = Graph::new();
+
+        graph.start().route(size_router()).on(Size::Small, |graph| {
+            graph.terminate();
+        });
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(50, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "size");
+        assert!(matches!(err.kind(), RunErrorKind::NoRoute(key) if key == "Medium"));
+        assert_eq!(err.into_state(), 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "already has a route for key Small")]
+    fn router_rejects_duplicate_keys() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .route(size_router())
+            .on(Size::Small, |graph| {
+                graph.terminate();
+            })
+            .on(Size::Small, |graph| {
+                graph.terminate();
+            });
+    }
+
+    #[test]
+    fn interrupt_suspends_until_resumed() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(adder(1))
+            .then(Interrupt::new("approve"))
+            .then(multiplier(3))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let Ok(RunOutcome::Interrupted(interrupted)) = runner.try_run(1, &RunContext::new()) else {
+            panic!("run should stop at the interrupt");
+        };
+
+        assert_eq!(
+            interrupted,
+            Interrupted {
+                node_id: NodeId(2),
+                state: 2,
+                run_id: None,
+            }
+        );
+
+        // the caller's input replaces the state before resuming
+        let result = runner.resume_interrupted(
+            Interrupted {
+                state: 10,
+                ..interrupted
+            },
+            &RunContext::new(),
+        );
+
+        assert_eq!(result.unwrap(), RunOutcome::Completed(30));
+    }
+
+    #[test]
+    fn interrupts_end_the_run_without_failing_it() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(Interrupt::new("approve"))
+            .then(adder(1))
+            .terminate();
+
+        let observer = Arc::new(RecordingObserver::default());
+        let metrics = Arc::new(MetricsRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(observer.clone()))
+            .with_observer(Box::new(metrics.clone()));
+
+        let outcome = runner.run(1, &RunContext::new());
+
+        assert_eq!(outcome.state(), &1);
+        assert_eq!(outcome.completed(), None);
+        assert_eq!(
+            observer.events.lock().unwrap().last().unwrap(),
+            "interrupted NodeId(1)"
+        );
+        assert_eq!(metrics.snapshot().nodes["approve"].failures, 0);
+    }
+
+    #[test]
+    fn interrupts_inside_subgraphs_fail_the_node() {
+        let mut inner = Graph::new();
+        inner
+            .start()
+            .then(Interrupt::new("approve"))
+            .then(adder(1))
+            .terminate();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Subgraph::new("inner", GraphRunner::new(inner).unwrap()))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        let RunErrorKind::Subgraph(inner) = err.kind() else {
+            panic!("expected a subgraph error, got {}", err.kind());
+        };
+        assert_eq!(inner.display_name(), "approve");
+        assert!(matches!(inner.kind(), RunErrorKind::Interrupted));
+    }
+
+    #[test]
+    fn resumed_interrupts_keep_saving_checkpoints() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(Interrupt::new("approve"))
+            .then(adder(1))
+            .terminate();
+
+        let store = Arc::new(MemoryCheckpointStore::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_checkpoints(Box::new(store.clone()));
+
+        let Ok(RunOutcome::Interrupted(interrupted)) =
+            runner.try_run_with_id("run-1", 1, &RunContext::new())
+        else {
+            panic!("run should stop at the interrupt");
+        };
+        assert_eq!(interrupted.run_id.as_deref(), Some("run-1"));
+
+        runner
+            .resume_interrupted(interrupted, &RunContext::new())
+            .unwrap();
+
+        // the last checkpoint is taken before the terminal, after resuming
+        let latest = store.latest("run-1").unwrap().unwrap();
+        assert_eq!((latest.node_id, latest.state), (NodeId(3), 2));
+    }
+
+    #[test]
+    fn resume_interrupted_rejects_other_nodes() {
+        let mut graph = Graph::new();
+        graph.start().then(adder(1)).terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner
+            .resume_interrupted(
+                Interrupted {
+                    node_id: NodeId(1),
+                    state: 0,
+                    run_id: None,
+                },
+                &RunContext::new(),
+            )
+            .unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::NotAnInterrupt));
+        assert_eq!(err.display_name(), "adder");
+    }
+
+    #[test]
+    fn subgraph_runs_as_single_node() {
+        let mut inner = Graph::new();
+        inner.start().then(adder(1)).then(multiplier(2)).terminate();
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Subgraph::new(
+                "add_then_double",
+                GraphRunner::new(inner).unwrap(),
+            ))
+            .then(adder(3))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(7));
+    }
+
+    #[test]
+    fn mapped_subgraph_uses_its_own_state_type() {
+        let mut inner = Graph::new();
+        inner
+            .start()
+            .then(Action::new("exclaim", Box::new(|text: String| text + "!")))
+            .terminate();
+
+        // the parent adds the length of the text the subgraph produced
+        let subgraph = Subgraph::mapped(
+            "exclaim",
+            GraphRunner::new(inner).unwrap(),
+            Box::new(|x: &i32| x.to_string()),
+            Box::new(|x, text| x + i32::try_from(text.len()).unwrap()),
+        );
+
+        let mut graph = Graph::new();
+        graph.start().then(subgraph).terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // "10" becomes "10!"
+        assert_eq!(
+            runner.run(10, &RunContext::new()),
+            RunOutcome::Completed(13)
+        );
+    }
+
+    #[test]
+    fn subgraph_reports_inner_failure_with_outer_state() {
+        let failing = Arc::new(AtomicBool::new(true));
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(adder(1))
+            .then(Subgraph::new(
+                "flaky_subgraph",
+                GraphRunner::new(flaky_graph(&failing)).unwrap(),
+            ))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "flaky_subgraph");
+        assert!(err.kind().is_retryable());
+
+        let RunErrorKind::Subgraph(inner) = err.kind() else {
+            panic!("expected a subgraph failure, got {}", err.kind());
+        };
+        assert_eq!(inner.display_name(), "flaky");
+        assert!(matches!(inner.kind(), RunErrorKind::Action(_)));
+
+        assert_eq!(err.into_state(), 2);
+    }
+
+    #[test]
+    fn subgraph_is_held_to_its_runners_limits() {
+        let mut inner = Graph::new();
+        counting_loop(&mut inner);
+
+        let inner_runner = GraphRunner::new(inner).unwrap().with_limits(RunLimits {
+            max_steps: Some(10),
+            ..RunLimits::default()
+        });
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Subgraph::new("counting_loop", inner_runner))
+            .terminate();
+
+        let err = GraphRunner::new(graph)
+            .unwrap()
+            .try_run(0, &RunContext::new())
+            .unwrap_err();
+
+        let RunErrorKind::Subgraph(inner) = err.kind() else {
+            panic!("expected a subgraph failure, got {}", err.kind());
+        };
+        assert!(matches!(
+            inner.kind(),
+            RunErrorKind::LimitExceeded(Limit::Steps(10))
+        ));
+
+        // the inner run went over its limits, so trying the subgraph again would not help
+        assert!(!err.kind().is_retryable());
+        assert_eq!(err.into_state(), 0);
+    }
+
+    #[test]
+    fn precondition_violation_names_node_and_check() {
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(adder(1).with_precondition(is_even()))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(2, &RunContext::new()), RunOutcome::Completed(3));
+
+        let err = runner.try_run(3, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "adder");
+        assert!(
+            matches!(err.kind(), RunErrorKind::PreconditionFailed(check) if check == "is_even")
+        );
+        assert_eq!(err.into_state(), 3);
+    }
+
+    #[test]
+    fn postcondition_violation_reports_prior_state() {
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(adder(1).with_postcondition(is_even()))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(2));
+
+        let err = runner.try_run(2, &RunContext::new()).unwrap_err();
+
+        assert!(
+            matches!(err.kind(), RunErrorKind::PostconditionFailed(check) if check == "is_even")
+        );
+        assert_eq!(err.into_state(), 2);
+    }
+
+    #[test]
+    fn postcondition_violation_is_retried() {
+        let attempts = Arc::new(AtomicI32::new(0));
+        let counter = attempts.clone();
+
+        // odd on the first attempt, even after that
+        let action = Action::new(
+            "flaky_adder",
+            Box::new(move |x| x + counter.fetch_add(1, Ordering::SeqCst) + 1),
+        )
+        .with_postcondition(is_even());
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(action)
+            .with_policy(NodePolicy {
+                max_attempts: 2,
+                ..NodePolicy::default()
+            })
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(0, &RunContext::new()), RunOutcome::Completed(2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn runner_is_shared_across_threads() {
+        let runner = Arc::new(GraphRunner::new(example_graph()).unwrap());
+
+        // spawn every run before waiting for any of them
+        let mut handles = Vec::new();
+        for input in 0..8 {
+            let runner = runner.clone();
+            handles.push(std::thread::spawn(move || {
+                runner.run(input, &RunContext::new())
+            }));
+        }
+
+        let outputs = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+
+        let expected = (0..8)
+            .map(|input| runner.run(input, &RunContext::new()))
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, expected);
+    }
+
+    #[test]
+    fn run_futures_are_send() {
+        fn assert_send(_: impl Send) {}
 
-        let result = runner.run(3);
+        let runner = GraphRunner::new(example_graph()).unwrap();
 
-        assert_eq!(result, 20);
+        assert_send(runner.run_async(1, &RunContext::new()));
+        assert_send(runner.run_stream(1, &RunContext::new()));
     }
 
     #[test]
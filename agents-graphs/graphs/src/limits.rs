@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::NodeId;
+
+/// Bounds on a single run, to stop graphs that loop forever.
+///
+/// Every node executed counts as a step, including the start and terminal nodes.
+/// The time budget is checked between nodes, so a node that hangs is not interrupted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunLimits {
+    pub max_steps: Option<usize>,
+    pub max_visits_per_node: Option<usize>,
+    pub time_budget: Option<Duration>,
+}
+
+/// The limit that stopped a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(usize),
+    NodeVisits(usize),
+    TimeBudget(Duration),
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Steps(max) => write!(f, "exceeded {max} steps"),
+            Self::NodeVisits(max) => write!(f, "exceeded {max} visits to the node"),
+            Self::TimeBudget(budget) => write!(f, "exceeded time budget of {budget:?}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    started: Instant,
+    steps: usize,
+    visits: HashMap<NodeId, usize>,
+}
+
+impl Usage {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            steps: 0,
+            visits: HashMap::new(),
+        }
+    }
+}
+
+/// Tracks how much of its limits a run has used, shared by all paths of the run.
+#[derive(Debug)]
+pub struct RunTracker {
+    limits: RunLimits,
+    usage: Mutex<Usage>,
+}
+
+impl RunTracker {
+    pub fn new(limits: RunLimits) -> Self {
+        Self {
+            limits,
+            usage: Mutex::new(Usage::new()),
+        }
+    }
+
+    /// Records a visit to `node_id`, failing if that goes over any limit.
+    pub fn visit(&self, node_id: NodeId) -> Result<(), Limit> {
+        let mut usage = self.usage.lock().expect("run tracker lock poisoned");
+
+        usage.steps += 1;
+        let visits = {
+            let visits = usage.visits.entry(node_id).or_default();
+            *visits += 1;
+            *visits
+        };
+
+        match self.limits {
+            RunLimits {
+                max_steps: Some(max),
+                ..
+            } if usage.steps > max => Err(Limit::Steps(max)),
+            RunLimits {
+                max_visits_per_node: Some(max),
+                ..
+            } if visits > max => Err(Limit::NodeVisits(max)),
+            RunLimits {
+                time_budget: Some(budget),
+                ..
+            } if usage.started.elapsed() > budget => Err(Limit::TimeBudget(budget)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Continues a resumed run, with `steps` already taken.
+    pub fn resumed(limits: RunLimits, steps: usize) -> Self {
+        let tracker = Self::new(limits);
+        tracker
+            .usage
+            .lock()
+            .expect("run tracker lock poisoned")
+            .steps = steps;
+        tracker
+    }
+
+    pub fn steps(&self) -> usize {
+        self.usage.lock().expect("run tracker lock poisoned").steps
+    }
+
+    /// Starts counting again from zero, giving the fallback path a fresh budget.
+    pub fn reset(&self) {
+        *self.usage.lock().expect("run tracker lock poisoned") = Usage::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{counting_loop, multiplier};
+    use crate::{Edge, Graph, GraphRunner, Node, RunContext, RunErrorKind, RunOutcome};
+
+    #[test]
+    fn step_limit_stops_loop() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let runner = GraphRunner::new(graph).unwrap().with_limits(RunLimits {
+            max_steps: Some(10),
+            ..RunLimits::default()
+        });
+
+        // START, then 5 rounds of adder and below_100, so the 11th step is below_100
+        let err = runner.try_run(0, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(
+            err.kind(),
+            RunErrorKind::LimitExceeded(Limit::Steps(10))
+        ));
+        assert_eq!(err.display_name(), "below_100");
+        assert_eq!(err.into_state(), 5);
+
+        // a run that fits within the limit is unaffected
+        assert_eq!(
+            runner.run(97, &RunContext::new()),
+            RunOutcome::Completed(100)
+        );
+    }
+
+    #[test]
+    fn visit_limit_stops_loop() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let runner = GraphRunner::new(graph).unwrap().with_limits(RunLimits {
+            max_visits_per_node: Some(3),
+            ..RunLimits::default()
+        });
+
+        let err = runner.try_run(0, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(
+            err.kind(),
+            RunErrorKind::LimitExceeded(Limit::NodeVisits(3))
+        ));
+        assert_eq!(err.into_state(), 3);
+    }
+
+    #[test]
+    fn time_budget_stops_loop() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let runner = GraphRunner::new(graph).unwrap().with_limits(RunLimits {
+            time_budget: Some(std::time::Duration::ZERO),
+            ..RunLimits::default()
+        });
+
+        let err = runner.try_run(0, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(
+            err.kind(),
+            RunErrorKind::LimitExceeded(Limit::TimeBudget(_))
+        ));
+    }
+
+    #[test]
+    fn exceeding_limit_continues_from_fallback() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let fallback = graph.register_node(multiplier(-1));
+        graph.add_node_from(fallback, Node::Terminal);
+        graph.set_limits_fallback(fallback);
+
+        let runner = GraphRunner::new(graph).unwrap().with_limits(RunLimits {
+            max_steps: Some(10),
+            ..RunLimits::default()
+        });
+
+        assert_eq!(runner.run(0, &RunContext::new()), RunOutcome::Completed(-5));
+    }
+
+    #[test]
+    fn fallback_is_only_taken_once() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        // the fallback leads straight back into the loop
+        let fallback = graph.register_node(multiplier(-1));
+        let loop_start = NodeId(1);
+        graph.edges.push(Edge {
+            from: fallback,
+            to: loop_start,
+            key: None,
+        });
+        graph.set_limits_fallback(fallback);
+
+        let runner = GraphRunner::new(graph).unwrap().with_limits(RunLimits {
+            max_steps: Some(10),
+            ..RunLimits::default()
+        });
+
+        let err = runner.try_run(0, &RunContext::new()).unwrap_err();
+
+        // the fallback turns 5 into -5, then the loop runs out of steps again at 0
+        assert!(matches!(
+            err.kind(),
+            RunErrorKind::LimitExceeded(Limit::Steps(10))
+        ));
+        assert_eq!(err.into_state(), 0);
+    }
+}
@@ -0,0 +1,464 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use crate::{NodeId, Observer, RunErrorKind, RunKey};
+
+/// The default latency buckets, the same as the Prometheus client libraries use.
+const DEFAULT_BUCKETS: [Duration; 11] = [
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(2500),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// How long a node took, bucketed by upper bound.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// The upper bounds of the buckets, in ascending order.
+    pub bounds: Vec<Duration>,
+    /// How many observations fell into each bucket, with one more entry at the end
+    /// for those above every bound.
+    pub counts: Vec<u64>,
+    pub sum: Duration,
+}
+
+impl Histogram {
+    fn new(bounds: &[Duration]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: Duration::ZERO,
+        }
+    }
+
+    fn observe(&mut self, elapsed: Duration) {
+        let bucket = self.bounds.partition_point(|bound| *bound < elapsed);
+        self.counts[bucket] += 1;
+        self.sum += elapsed;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// What happened to every node with the same display name, across runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeMetrics {
+    /// How many times the node was started.
+    pub runs: u64,
+    /// How many times the node was tried, counting retries by its policy.
+    pub attempts: u64,
+    /// How many attempts at the node failed, including ones that were retried or recovered
+    /// from through a fallback. Runs stopped by their limits or by cancellation are not
+    /// failures of the node they stopped at.
+    pub failures: u64,
+    pub branches_true: u64,
+    pub branches_false: u64,
+    /// How long the node took, when it completed.
+    pub latency: Histogram,
+}
+
+impl NodeMetrics {
+    fn new(bounds: &[Duration]) -> Self {
+        Self {
+            runs: 0,
+            attempts: 0,
+            failures: 0,
+            branches_true: 0,
+            branches_false: 0,
+            latency: Histogram::new(bounds),
+        }
+    }
+}
+
+/// The metrics of every node seen so far, by display name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    pub nodes: BTreeMap<String, NodeMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// Renders the metrics in the Prometheus text exposition format, labelled by node name.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+
+        self.write_counter(
+            &mut text,
+            "graph_node_runs_total",
+            "Times the node was started.",
+            |metrics| metrics.runs,
+        );
+        self.write_counter(
+            &mut text,
+            "graph_node_attempts_total",
+            "Times the node was tried, including retries.",
+            |metrics| metrics.attempts,
+        );
+        self.write_counter(
+            &mut text,
+            "graph_node_failures_total",
+            "Attempts at the node that failed.",
+            |metrics| metrics.failures,
+        );
+
+        let _ = writeln!(
+            text,
+            "# HELP graph_node_branches_total Outcomes of the node's condition."
+        );
+        let _ = writeln!(text, "# TYPE graph_node_branches_total counter");
+        for (name, metrics) in self.branching_nodes() {
+            let name = escape_label(name);
+
+            for (taken, count) in [
+                ("true", metrics.branches_true),
+                ("false", metrics.branches_false),
+            ] {
+                let _ = writeln!(
+                    text,
+                    "graph_node_branches_total{{node=\"{name}\",taken=\"{taken}\"}} {count}"
+                );
+            }
+        }
+
+        let _ = writeln!(
+            text,
+            "# HELP graph_node_latency_seconds Time the node took to complete."
+        );
+        let _ = writeln!(text, "# TYPE graph_node_latency_seconds histogram");
+        for (name, metrics) in &self.nodes {
+            let name = escape_label(name);
+            let latency = &metrics.latency;
+
+            // Prometheus buckets are cumulative
+            let mut cumulative = 0;
+            for (bound, count) in latency.bounds.iter().zip(&latency.counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "graph_node_latency_seconds_bucket{{node=\"{name}\",le=\"{}\"}} {cumulative}",
+                    bound.as_secs_f64()
+                );
+            }
+
+            let _ = writeln!(
+                text,
+                "graph_node_latency_seconds_bucket{{node=\"{name}\",le=\"+Inf\"}} {}",
+                latency.count()
+            );
+            let _ = writeln!(
+                text,
+                "graph_node_latency_seconds_sum{{node=\"{name}\"}} {}",
+                latency.sum.as_secs_f64()
+            );
+            let _ = writeln!(
+                text,
+                "graph_node_latency_seconds_count{{node=\"{name}\"}} {}",
+                latency.count()
+            );
+        }
+
+        text
+    }
+
+    fn write_counter(
+        &self,
+        text: &mut String,
+        metric: &str,
+        help: &str,
+        value: impl Fn(&NodeMetrics) -> u64,
+    ) {
+        let _ = writeln!(text, "# HELP {metric} {help}");
+        let _ = writeln!(text, "# TYPE {metric} counter");
+
+        for (name, metrics) in &self.nodes {
+            let _ = writeln!(
+                text,
+                "{metric}{{node=\"{}\"}} {}",
+                escape_label(name),
+                value(metrics)
+            );
+        }
+    }
+
+    fn branching_nodes(&self) -> impl Iterator<Item = (&String, &NodeMetrics)> {
+        self.nodes
+            .iter()
+            .filter(|(_, metrics)| metrics.branches_true + metrics.branches_false > 0)
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// An observer that aggregates node metrics across every run it sees.
+///
+/// Nodes are grouped by display name, so the same action used in several places,
+/// or in several graphs sharing the recorder, is counted together.
+#[derive(Debug)]
+pub struct MetricsRecorder {
+    buckets: Vec<Duration>,
+    nodes: Mutex<BTreeMap<String, NodeMetrics>>,
+}
+
+impl MetricsRecorder {
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS.to_vec())
+    }
+
+    /// Uses the given upper bounds for the latency histograms instead of the defaults.
+    pub fn with_buckets(mut buckets: Vec<Duration>) -> Self {
+        buckets.sort();
+        buckets.dedup();
+
+        Self {
+            buckets,
+            nodes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            nodes: self.lock().clone(),
+        }
+    }
+
+    fn update(&self, display_name: &str, update: impl FnOnce(&mut NodeMetrics)) {
+        update(
+            self.lock()
+                .entry(display_name.to_string())
+                .or_insert_with(|| NodeMetrics::new(&self.buckets)),
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, NodeMetrics>> {
+        self.nodes.lock().expect("metrics recorder lock poisoned")
+    }
+}
+
+impl Default for MetricsRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Observer<T> for MetricsRecorder {
+    fn on_node_start(&self, _run: RunKey, _node_id: NodeId, display_name: &str, _state: &T) {
+        self.update(display_name, |metrics| {
+            metrics.runs += 1;
+            metrics.attempts += 1;
+        });
+    }
+
+    fn on_node_end(
+        &self,
+        _run: RunKey,
+        _node_id: NodeId,
+        display_name: &str,
+        _state: &T,
+        elapsed: Duration,
+    ) {
+        self.update(display_name, |metrics| metrics.latency.observe(elapsed));
+    }
+
+    fn on_node_failed(
+        &self,
+        _run: RunKey,
+        _node_id: NodeId,
+        display_name: &str,
+        _error: &RunErrorKind,
+        retrying: bool,
+    ) {
+        self.update(display_name, |metrics| {
+            metrics.failures += 1;
+            metrics.attempts += u64::from(retrying);
+        });
+    }
+
+    fn on_branch(&self, _run: RunKey, _node_id: NodeId, display_name: &str, taken: bool) {
+        self.update(display_name, |metrics| {
+            if taken {
+                metrics.branches_true += 1;
+            } else {
+                metrics.branches_false += 1;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_support::{adder, counting_loop, example_graph, fails_first, model_then_tool};
+    use crate::{Graph, GraphRunner, NodePolicy, RunContext, RunLimits, RunOutcome};
+
+    #[test]
+    fn metrics_aggregate_across_runs() {
+        let metrics = Arc::new(MetricsRecorder::new());
+        let runner = GraphRunner::new(example_graph())
+            .unwrap()
+            .with_observer(Box::new(metrics.clone()));
+
+        runner.run(8, &RunContext::new());
+        runner.run(10, &RunContext::new());
+
+        let snapshot = metrics.snapshot();
+
+        // 8 loops through adder twice before leaving, 10 once
+        let small = &snapshot.nodes["is \"small\""];
+        assert_eq!((small.branches_true, small.branches_false), (1, 2));
+        assert_eq!(small.runs, 3);
+        assert_eq!(small.latency.count(), 3);
+
+        // the adder in the loop and the one in the fan-out share a name
+        assert_eq!(snapshot.nodes["adder"].runs, 4);
+        assert_eq!(snapshot.nodes["multiplier"].runs, 1);
+    }
+
+    #[test]
+    fn metrics_count_failures() {
+        let metrics = Arc::new(MetricsRecorder::new());
+        let runner = GraphRunner::new(model_then_tool(adder(1)))
+            .unwrap()
+            .with_observer(Box::new(metrics.clone()));
+
+        assert!(runner.try_run(1, &RunContext::new()).is_ok());
+        assert!(runner.try_run(2, &RunContext::new()).is_err());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.nodes["tool"].runs, 2);
+        assert_eq!(snapshot.nodes["tool"].failures, 1);
+        assert_eq!(snapshot.nodes["tool"].latency.count(), 1);
+        assert_eq!(snapshot.nodes["adder"].failures, 0);
+    }
+
+    #[test]
+    fn metrics_count_every_failed_attempt() {
+        let (action, _) = fails_first(1);
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(action)
+            .with_policy(NodePolicy {
+                max_attempts: 2,
+                ..NodePolicy::default()
+            })
+            .terminate();
+
+        let metrics = Arc::new(MetricsRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(metrics.clone()));
+
+        // the retry succeeds, but the first attempt still counts as a failure
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(1));
+
+        let fails_first = &metrics.snapshot().nodes["fails_first"];
+        assert_eq!(
+            (fails_first.runs, fails_first.attempts, fails_first.failures),
+            (1, 2, 1)
+        );
+    }
+
+    #[test]
+    fn metrics_do_not_blame_nodes_for_run_limits() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let metrics = Arc::new(MetricsRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_limits(RunLimits {
+                max_steps: Some(5),
+                ..RunLimits::default()
+            })
+            .with_observer(Box::new(metrics.clone()));
+
+        assert!(runner.try_run(0, &RunContext::new()).is_err());
+
+        let snapshot = metrics.snapshot();
+        assert!(snapshot.nodes.values().all(|node| node.failures == 0));
+        assert!(!snapshot.nodes.contains_key("Terminal"));
+    }
+
+    #[test]
+    fn metrics_render_as_prometheus_text() {
+        let mut latency = Histogram {
+            bounds: vec![Duration::from_millis(10), Duration::from_secs(1)],
+            counts: vec![1, 2, 1],
+            sum: Duration::from_millis(2500),
+        };
+
+        let mut snapshot = MetricsSnapshot::default();
+        snapshot.nodes.insert(
+            "is \"small\"".to_string(),
+            NodeMetrics {
+                runs: 4,
+                attempts: 5,
+                failures: 1,
+                branches_true: 3,
+                branches_false: 0,
+                latency: latency.clone(),
+            },
+        );
+
+        latency.counts = vec![0, 0, 0];
+        latency.sum = Duration::ZERO;
+        snapshot.nodes.insert(
+            "tool".to_string(),
+            NodeMetrics {
+                runs: 0,
+                attempts: 0,
+                failures: 0,
+                branches_true: 0,
+                branches_false: 0,
+                latency,
+            },
+        );
+
+        let expected = r#"# HELP graph_node_runs_total Times the node was started.
+# TYPE graph_node_runs_total counter
+graph_node_runs_total{node="is \"small\""} 4
+graph_node_runs_total{node="tool"} 0
+# HELP graph_node_attempts_total Times the node was tried, including retries.
+# TYPE graph_node_attempts_total counter
+graph_node_attempts_total{node="is \"small\""} 5
+graph_node_attempts_total{node="tool"} 0
+# HELP graph_node_failures_total Attempts at the node that failed.
+# TYPE graph_node_failures_total counter
+graph_node_failures_total{node="is \"small\""} 1
+graph_node_failures_total{node="tool"} 0
+# HELP graph_node_branches_total Outcomes of the node's condition.
+# TYPE graph_node_branches_total counter
+graph_node_branches_total{node="is \"small\"",taken="true"} 3
+graph_node_branches_total{node="is \"small\"",taken="false"} 0
+# HELP graph_node_latency_seconds Time the node took to complete.
+# TYPE graph_node_latency_seconds histogram
+graph_node_latency_seconds_bucket{node="is \"small\"",le="0.01"} 1
+graph_node_latency_seconds_bucket{node="is \"small\"",le="1"} 3
+graph_node_latency_seconds_bucket{node="is \"small\"",le="+Inf"} 4
+graph_node_latency_seconds_sum{node="is \"small\""} 2.5
+graph_node_latency_seconds_count{node="is \"small\""} 4
+graph_node_latency_seconds_bucket{node="tool",le="0.01"} 0
+graph_node_latency_seconds_bucket{node="tool",le="1"} 0
+graph_node_latency_seconds_bucket{node="tool",le="+Inf"} 0
+graph_node_latency_seconds_sum{node="tool"} 0
+graph_node_latency_seconds_count{node="tool"} 0
+"#;
+
+        assert_eq!(snapshot.to_prometheus(), expected);
+    }
+}
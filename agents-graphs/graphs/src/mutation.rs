@@ -0,0 +1,276 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{Edge, Graph, IdentifiedNode, Node, NodeId};
+
+/// A reason a graph could not be changed. The graph is left as it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MutationError {
+    /// The node is not part of the graph, or was removed.
+    UnknownNode(NodeId),
+    /// The start node cannot be replaced or removed.
+    StartNode,
+    /// There is no edge between the two nodes.
+    NoEdge { from: NodeId, to: NodeId },
+    /// The node has edges leading to it, but not exactly one successor to lead them to instead.
+    CannotRewire(NodeId),
+    /// The node is the join of a fan-out, or the fallback of a policy or of the limits.
+    InUse(NodeId),
+}
+
+impl Display for MutationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownNode(node_id) => write!(f, "node {node_id:?} is not part of the graph"),
+            Self::StartNode => write!(f, "the start node cannot be changed"),
+            Self::NoEdge { from, to } => write!(f, "no edge from {from:?} to {to:?}"),
+            Self::CannotRewire(node_id) => {
+                write!(
+                    f,
+                    "node {node_id:?} does not have a single successor to rewire to"
+                )
+            }
+            Self::InUse(node_id) => write!(f, "node {node_id:?} is used as a join or fallback"),
+        }
+    }
+}
+
+impl Error for MutationError {}
+
+/// Changes to a graph after it was built, e.g. to swap a node for a stub in tests.
+///
+/// Changes can leave the graph invalid, such as a branch replaced by an action that
+/// now has two edges; `GraphRunner::new` reports that when the graph is run.
+impl<T> Graph<T> {
+    /// Puts `node` in place of the node with the given id, keeping its edges and policy,
+    /// and returns the node it replaced.
+    pub fn replace_node(
+        &mut self,
+        node_id: NodeId,
+        node: impl Into<Node<T>>,
+    ) -> Result<Node<T>, MutationError> {
+        if node_id == self.start_id {
+            return Err(MutationError::StartNode);
+        }
+
+        let identified = self
+            .nodes
+            .get_mut(&node_id)
+            .ok_or(MutationError::UnknownNode(node_id))?;
+
+        Ok(std::mem::replace(&mut identified.node, node.into()))
+    }
+
+    /// Adds `node` on the edge from `from` to `to`, which then leads through it.
+    ///
+    /// The edge keeps its place among the edges of `from`, and its router key if it has one,
+    /// so the new node is on the same side of a branch or route as `to` was.
+    pub fn insert_between(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        node: impl Into<Node<T>>,
+    ) -> Result<NodeId, MutationError> {
+        let edge = self.edge_index(from, to)?;
+
+        let inserted = self.register_node(node);
+        self.edges[edge].to = inserted;
+        self.edges.push(Edge {
+            from: inserted,
+            to,
+            key: None,
+        });
+
+        Ok(inserted)
+    }
+
+    /// Removes every edge from `from` to `to`.
+    pub fn remove_edge(&mut self, from: NodeId, to: NodeId) -> Result<(), MutationError> {
+        self.edge_index(from, to)?;
+
+        self.edges.retain(|edge| edge.from != from || edge.to != to);
+
+        Ok(())
+    }
+
+    /// Removes the node and its policy, leading the edges that pointed at it to its successor,
+    /// and returns the removed node.
+    ///
+    /// A node without edges leading to it, such as a goto target, can always be removed.
+    /// Its id is never handed out again.
+    pub fn remove_node(&mut self, node_id: NodeId) -> Result<Node<T>, MutationError> {
+        if node_id == self.start_id {
+            return Err(MutationError::StartNode);
+        }
+
+        if !self.nodes.contains_key(&node_id) {
+            return Err(MutationError::UnknownNode(node_id));
+        }
+
+        let is_join = self
+            .nodes
+            .values()
+            .any(|n| matches!(n.node, Node::FanOut { join } if join == node_id));
+        let is_fallback = self.limits_fallback == Some(node_id)
+            || self
+                .policies
+                .values()
+                .any(|policy| policy.fallback == Some(node_id));
+
+        if is_join || is_fallback {
+            return Err(MutationError::InUse(node_id));
+        }
+
+        let has_incoming = self.edges.iter().any(|edge| edge.to == node_id);
+
+        let successors = self.next_nodes(node_id).collect::<Vec<_>>();
+        let successor = match successors.as_slice() {
+            [successor] if *successor != node_id => Some(*successor),
+            _ if has_incoming => return Err(MutationError::CannotRewire(node_id)),
+            _ => None,
+        };
+
+        self.edges.retain(|edge| edge.from != node_id);
+
+        if let Some(successor) = successor {
+            for edge in self.edges.iter_mut().filter(|edge| edge.to == node_id) {
+                edge.to = successor;
+            }
+        }
+
+        self.policies.remove(&node_id);
+
+        let IdentifiedNode { node, .. } = self
+            .nodes
+            .remove(&node_id)
+            .expect("node was checked to exist");
+
+        Ok(node)
+    }
+
+    fn edge_index(&self, from: NodeId, to: NodeId) -> Result<usize, MutationError> {
+        self.edges
+            .iter()
+            .position(|edge| edge.from == from && edge.to == to)
+            .ok_or(MutationError::NoEdge { from, to })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{adder, example_graph, model_then_tool, multiplier};
+    use crate::{Action, GraphRunner, IssueKind, RunContext, RunErrorKind, RunOutcome};
+
+    #[test]
+    fn replace_node_swaps_in_a_stub() {
+        let mut graph = model_then_tool(adder(1));
+
+        let replaced = graph
+            .replace_node(NodeId(1), Action::new("stub", Box::new(|x| x * 2)))
+            .unwrap();
+        assert_eq!(replaced.display_name(), "adder");
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(2));
+    }
+
+    #[test]
+    fn insert_between_keeps_the_branch_side() {
+        let mut graph = example_graph();
+
+        // on the true side of "is small", back to the adder
+        graph
+            .insert_between(NodeId(2), NodeId(1), multiplier(2))
+            .unwrap();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // 1 -> 2 -> 4 -> 5 -> 10 -> 11, then the fan-out sums 12 and 11
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(23));
+    }
+
+    #[test]
+    fn remove_node_rewires_incoming_edges() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1)).then(multiplier(3)).terminate();
+
+        let removed = graph.remove_node(NodeId(1)).unwrap();
+        assert_eq!(removed.display_name(), "adder");
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(3));
+    }
+
+    #[test]
+    fn edges_from_removed_nodes_are_reported() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1)).then(multiplier(3)).terminate();
+
+        let removed = NodeId(1);
+        graph.remove_node(removed).unwrap();
+        graph.add_node_from(removed, Node::Terminal);
+        let orphan = NodeId(4);
+
+        let definition = graph.to_definition();
+        assert!(definition.edges.iter().any(|edge| edge.from == "n1"));
+
+        let err = graph.validate().unwrap_err();
+
+        assert!(err.issues().iter().any(
+            |issue| issue.node_id == orphan && issue.kind == IssueKind::UnknownSource(removed)
+        ));
+    }
+
+    #[test]
+    fn mutations_report_what_they_cannot_do() {
+        let mut graph = example_graph();
+
+        assert_eq!(
+            graph.remove_node(NodeId(0)).unwrap_err(),
+            MutationError::StartNode
+        );
+        assert_eq!(
+            graph.remove_node(NodeId(5)).unwrap_err(),
+            MutationError::CannotRewire(NodeId(5))
+        );
+        assert_eq!(
+            graph.remove_node(NodeId(6)).unwrap_err(),
+            MutationError::InUse(NodeId(6))
+        );
+        assert_eq!(
+            graph.remove_edge(NodeId(1), NodeId(3)),
+            Err(MutationError::NoEdge {
+                from: NodeId(1),
+                to: NodeId(3)
+            })
+        );
+        assert!(matches!(
+            graph.replace_node(NodeId(42), adder(1)),
+            Err(MutationError::UnknownNode(NodeId(42)))
+        ));
+
+        // nothing was changed
+        assert_eq!(graph.to_definition(), example_graph().to_definition());
+    }
+
+    #[test]
+    fn removing_a_route_leaves_a_runnable_graph() {
+        let mut graph = example_graph();
+
+        graph.remove_edge(NodeId(3), NodeId(4)).unwrap();
+        graph.remove_node(NodeId(4)).unwrap();
+        graph.remove_node(NodeId(5)).unwrap();
+
+        // the router lost its "0" route, but what is left is still a valid graph
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert!(matches!(
+            runner.try_run(8, &RunContext::new()).unwrap_err().kind(),
+            RunErrorKind::NoRoute(key) if key == "0"
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{NodeId, RunError, RunErrorKind, RunOutcome};
+
+/// Tells apart the runs an observer sees, e.g. runs of a shared runner that execute concurrently.
+///
+/// Every run gets a new key, including resumed, forked and replayed runs, unique within the process.
+/// The paths of a fan-out are part of the run they belong to, so they share its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RunKey(u64);
+
+impl RunKey {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Receives callbacks from a `GraphRunner` as it executes a graph.
+///
+/// Every method has an empty default, so implementors only override what they need.
+/// Each callback names the run it is about, since one observer can see several runs at once.
+pub trait Observer<T>: Send + Sync {
+    fn on_node_start(&self, _run: RunKey, _node_id: NodeId, _display_name: &str, _state: &T) {}
+
+    /// Called after a node completes successfully, with the state it produced.
+    fn on_node_end(
+        &self,
+        _run: RunKey,
+        _node_id: NodeId,
+        _display_name: &str,
+        _state: &T,
+        _elapsed: Duration,
+    ) {
+    }
+
+    /// Called every time an attempt at a node fails, with whether the node is tried again.
+    ///
+    /// Attempts retried by the node's policy, or recovered from through its fallback, are
+    /// included. Runs stopped by their limits or by cancellation are not failures of a node,
+    /// so they only show up in `on_run_end`.
+    fn on_node_failed(
+        &self,
+        _run: RunKey,
+        _node_id: NodeId,
+        _display_name: &str,
+        _error: &RunErrorKind,
+        _retrying: bool,
+    ) {
+    }
+
+    fn on_branch(&self, _run: RunKey, _node_id: NodeId, _display_name: &str, _taken: bool) {}
+
+    fn on_route(&self, _run: RunKey, _node_id: NodeId, _display_name: &str, _key: &str) {}
+
+    /// Called when an action jumps to `target` instead of following its edge.
+    fn on_goto(&self, _run: RunKey, _node_id: NodeId, _display_name: &str, _target: NodeId) {}
+
+    /// Called once the run completes, stops at an interrupt, or fails.
+    fn on_run_end(&self, _run: RunKey, _result: Result<&RunOutcome<T>, &RunError<T>>) {}
+}
+
+// Lets callers keep a handle to an observer after registering it.
+impl<T, O: Observer<T> + ?Sized> Observer<T> for Arc<O> {
+    fn on_node_start(&self, run: RunKey, node_id: NodeId, display_name: &str, state: &T) {
+        (**self).on_node_start(run, node_id, display_name, state);
+    }
+
+    fn on_node_end(
+        &self,
+        run: RunKey,
+        node_id: NodeId,
+        display_name: &str,
+        state: &T,
+        elapsed: Duration,
+    ) {
+        (**self).on_node_end(run, node_id, display_name, state, elapsed);
+    }
+
+    fn on_node_failed(
+        &self,
+        run: RunKey,
+        node_id: NodeId,
+        display_name: &str,
+        error: &RunErrorKind,
+        retrying: bool,
+    ) {
+        (**self).on_node_failed(run, node_id, display_name, error, retrying);
+    }
+
+    fn on_branch(&self, run: RunKey, node_id: NodeId, display_name: &str, taken: bool) {
+        (**self).on_branch(run, node_id, display_name, taken);
+    }
+
+    fn on_route(&self, run: RunKey, node_id: NodeId, display_name: &str, key: &str) {
+        (**self).on_route(run, node_id, display_name, key);
+    }
+
+    fn on_goto(&self, run: RunKey, node_id: NodeId, display_name: &str, target: NodeId) {
+        (**self).on_goto(run, node_id, display_name, target);
+    }
+
+    fn on_run_end(&self, run: RunKey, result: Result<&RunOutcome<T>, &RunError<T>>) {
+        (**self).on_run_end(run, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{RecordingObserver, Size, adder, size_router};
+    use crate::{Action, Condition, Graph, GraphRunner, RunContext};
+
+    #[test]
+    fn observer_sees_node_events_in_order() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1)).branch(
+            Condition::new("is_even", Box::new(|x| x % 2 == 0)),
+            |graph| {
+                graph.route(size_router()).on(Size::Small, |graph| {
+                    graph.terminate();
+                });
+            },
+            |graph| {
+                graph
+                    .then(Action::new_fallible(
+                        "fails",
+                        Box::new(|_| Err("odd".into())),
+                    ))
+                    .terminate();
+            },
+        );
+
+        let observer = std::sync::Arc::new(RecordingObserver::default());
+
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(observer.clone()));
+
+        runner.run(1, &RunContext::new());
+        runner.try_run(2, &RunContext::new()).unwrap_err();
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            vec![
+                "start NodeId(0) START 1",
+                "end NodeId(0) START 1",
+                "start NodeId(1) adder 1",
+                "end NodeId(1) adder 2",
+                "start NodeId(2) is_even 2",
+                "branch is_even true",
+                "end NodeId(2) is_even 2",
+                "start NodeId(3) size 2",
+                "route size Small",
+                "end NodeId(3) size 2",
+                "start NodeId(4) Terminal 2",
+                "end NodeId(4) Terminal 2",
+                "done 2",
+                "start NodeId(0) START 2",
+                "end NodeId(0) START 2",
+                "start NodeId(1) adder 2",
+                "end NodeId(1) adder 3",
+                "start NodeId(2) is_even 3",
+                "branch is_even false",
+                "end NodeId(2) is_even 3",
+                "start NodeId(5) fails 3",
+                "failed fails",
+            ]
+        );
+    }
+}
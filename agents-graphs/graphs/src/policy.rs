@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use crate::NodeId;
+
+/// How the runner retries a node that fails, attached with `GraphAdding::with_policy`.
+///
+/// Only failures of the node itself are retried: actions and conditions that return an error,
+/// subgraphs whose inner graph failed that way, and attempts that time out.
+/// Every attempt is given the same state.
+#[derive(Debug, Clone, Copy)]
+pub struct NodePolicy {
+    /// Attempts in total, including the first.
+    pub max_attempts: usize,
+    /// The delay before the first retry, doubled before each retry after that.
+    pub backoff: Duration,
+    /// How long a single attempt may take.
+    ///
+    /// Only async nodes can be cut short; sync nodes run inline, so they always run to completion.
+    pub timeout: Option<Duration>,
+    /// The node the run continues from once every attempt has failed, instead of failing the run.
+    pub fallback: Option<NodeId>,
+}
+
+impl Default for NodePolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Duration::ZERO,
+            timeout: None,
+            fallback: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::test_support::{Size, adder, fails_first, multiplier, size_router, sum};
+    use crate::{Action, Graph, GraphRunner, Node, RunContext, RunErrorKind, RunOutcome};
+
+    #[test]
+    fn policy_retries_failing_node() {
+        let (action, attempts) = fails_first(2);
+
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(action)
+            .with_policy(NodePolicy {
+                max_attempts: 3,
+                backoff: std::time::Duration::from_millis(1),
+                ..NodePolicy::default()
+            })
+            .then(adder(1))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn policy_continues_from_fallback_after_last_attempt() {
+        let (action, attempts) = fails_first(usize::MAX);
+
+        let mut graph = Graph::new();
+        let fallback = graph.register_node(multiplier(-1));
+        graph.add_node_from(fallback, Node::Terminal);
+
+        graph
+            .start()
+            .then(adder(1))
+            .then(action)
+            .with_policy(NodePolicy {
+                max_attempts: 2,
+                fallback: Some(fallback),
+                ..NodePolicy::default()
+            })
+            .then(multiplier(3))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // the fallback gets the state the failing node was given
+        assert_eq!(runner.run(1, &RunContext::new()), RunOutcome::Completed(-2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn policy_falls_back_with_state_given_to_fan_out() {
+        let mut graph = Graph::new();
+        let fallback = graph.register_node(adder(1000));
+        graph.add_node_from(fallback, Node::Terminal);
+
+        graph
+            .start()
+            .fan_out(sum())
+            .path(|path| {
+                path.then(adder(10)).then(Action::new_fallible(
+                    "fails",
+                    Box::new(|_| Err("path failed".into())),
+                ))
+            })
+            .join()
+            .terminate();
+
+        // the join is registered before the fan-out
+        graph.set_policy(
+            NodeId(4),
+            NodePolicy {
+                max_attempts: 1,
+                fallback: Some(fallback),
+                ..NodePolicy::default()
+            },
+        );
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        // the fallback gets the fan-out's input, not the failing path's state
+        assert_eq!(
+            runner.run(1, &RunContext::new()),
+            RunOutcome::Completed(1001)
+        );
+    }
+
+    #[test]
+    fn policy_times_out_async_node() {
+        let mut graph = Graph::new();
+        graph
+            .start()
+            .then(Action::new_async(
+                "hangs",
+                Box::new(|_| Box::pin(futures::future::pending())),
+            ))
+            .with_policy(NodePolicy {
+                max_attempts: 2,
+                timeout: Some(std::time::Duration::from_millis(10)),
+                ..NodePolicy::default()
+            })
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(1, &RunContext::new()).unwrap_err();
+
+        assert_eq!(err.display_name(), "hangs");
+        assert!(matches!(err.kind(), RunErrorKind::TimedOut(_)));
+        assert_eq!(err.into_state(), 1);
+    }
+
+    #[test]
+    fn policy_does_not_retry_graph_errors() {
+        let mut graph = Graph::new();
+        graph.start().route(size_router()).on(Size::Small, |graph| {
+            graph.terminate();
+        });
+
+        let (fallback, _) = fails_first(0);
+        let fallback = graph.register_node(fallback);
+        graph.add_node_from(fallback, Node::Terminal);
+        graph.set_policy(
+            NodeId(1),
+            NodePolicy {
+                max_attempts: 3,
+                fallback: Some(fallback),
+                ..NodePolicy::default()
+            },
+        );
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let err = runner.try_run(500, &RunContext::new()).unwrap_err();
+
+        assert!(matches!(err.kind(), RunErrorKind::NoRoute(_)));
+    }
+}
@@ -0,0 +1,289 @@
+use std::{any::Any, sync::Mutex};
+
+use crate::{Action, ActionFn, BoxError, Join, JoinFn};
+
+/// State that nodes can change with partial updates instead of returning it whole.
+///
+/// `reduce` merges an update into the state, usually field by field with
+/// reducers like `append` and `overwrite`:
+///
+/// ```
+/// use graphs::{Reduce, append, overwrite};
+///
+/// struct State {
+///     messages: Vec<String>,
+///     turn: usize,
+/// }
+///
+/// #[derive(Default)]
+/// struct Update {
+///     messages: Vec<String>,
+///     turn: Option<usize>,
+/// }
+///
+/// impl Reduce for State {
+///     type Update = Update;
+///
+///     fn reduce(self, update: Update) -> Self {
+///         Self {
+///             messages: append(self.messages, update.messages),
+///             turn: overwrite(self.turn, update.turn),
+///         }
+///     }
+/// }
+/// ```
+pub trait Reduce: Sized {
+    type Update;
+
+    fn reduce(self, update: Self::Update) -> Self;
+}
+
+/// Reducer for list fields: the update's items are added after the current ones.
+pub fn append<V>(mut current: Vec<V>, update: Vec<V>) -> Vec<V> {
+    current.extend(update);
+    current
+}
+
+/// Reducer for scalar fields: the update replaces the current value, if there is one.
+pub fn overwrite<V>(current: V, update: Option<V>) -> V {
+    update.unwrap_or(current)
+}
+
+type UpdateFn<T> = Box<dyn Fn(&T) -> <T as Reduce>::Update + Send + Sync>;
+type FallibleUpdateFn<T> = Box<dyn Fn(&T) -> Result<<T as Reduce>::Update, BoxError> + Send + Sync>;
+
+/// An update an `Action::update` node emitted inside a fan-out path, kept for the join.
+pub type KeptUpdate = Box<dyn Any + Send>;
+/// The updates kept by one path of a fan-out, in the order its nodes emitted them.
+pub type UpdateLog = Mutex<Vec<KeptUpdate>>;
+/// Applies an update action; the flag asks it to also hand back a copy of the update.
+pub type UpdateActionFn<T> =
+    Box<dyn Fn(T, bool) -> Result<(T, Option<KeptUpdate>), BoxError> + Send + Sync>;
+/// Reduces the updates kept by every path into the state the fan-out started from.
+pub type ReduceFn<T> = Box<dyn Fn(T, &[KeptUpdate]) -> T + Send + Sync>;
+
+impl<T: Reduce + 'static> Action<T>
+where
+    T::Update: Clone + Send,
+{
+    /// Creates an action that returns only what changed, which is reduced into the state.
+    ///
+    /// Inside the paths of a fan-out that ends at a `Join::reducing`, the update is also
+    /// kept for the join.
+    pub fn update(display_name: impl Into<String>, update: UpdateFn<T>) -> Self {
+        Self::with_fn(
+            display_name,
+            ActionFn::Update(Box::new(move |state, keep| {
+                let update = update(&state);
+                Ok(apply(state, update, keep))
+            })),
+        )
+    }
+
+    /// Like `Action::update`, for updates that can fail.
+    pub fn update_fallible(display_name: impl Into<String>, update: FallibleUpdateFn<T>) -> Self {
+        Self::with_fn(
+            display_name,
+            ActionFn::Update(Box::new(move |state, keep| {
+                let update = update(&state)?;
+                Ok(apply(state, update, keep))
+            })),
+        )
+    }
+}
+
+fn apply<T: Reduce>(state: T, update: T::Update, keep: bool) -> (T, Option<KeptUpdate>)
+where
+    T::Update: Clone + Send + 'static,
+{
+    let kept = keep.then(|| Box::new(update.clone()) as KeptUpdate);
+    (state.reduce(update), kept)
+}
+
+impl<T: Reduce + 'static> Join<T>
+where
+    T::Update: Clone,
+{
+    /// Creates a join that reduces the updates emitted by `Action::update` nodes in every path
+    /// into the state the fan-out started from, path by path in the order the paths were added.
+    ///
+    /// Only updates reach the join: whatever other actions change in a path's state is dropped.
+    /// The updates kept by a fan-out nested inside a path count as updates of that path.
+    /// When a trace is replayed, update actions inside these paths run again instead of
+    /// replaying their recorded state, so the join gets their updates.
+    pub fn reducing(display_name: impl Into<String>) -> Self {
+        Self::with_fn(
+            display_name,
+            JoinFn::Reduce(Box::new(|base, updates| {
+                updates
+                    .iter()
+                    .filter_map(|update| update.downcast_ref::<T::Update>())
+                    .cloned()
+                    .fold(base, Reduce::reduce)
+            })),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Graph, GraphRunner, RunContext};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Log {
+        entries: Vec<String>,
+        last: String,
+    }
+
+    #[derive(Clone, Default)]
+    struct LogUpdate {
+        entries: Vec<String>,
+        last: Option<String>,
+    }
+
+    impl Reduce for Log {
+        type Update = LogUpdate;
+
+        fn reduce(self, update: LogUpdate) -> Self {
+            Self {
+                entries: append(self.entries, update.entries),
+                last: overwrite(self.last, update.last),
+            }
+        }
+    }
+
+    fn logger(entry: &'static str) -> Action<Log> {
+        Action::update(
+            entry,
+            Box::new(move |_| LogUpdate {
+                entries: vec![entry.to_string()],
+                last: Some(entry.to_string()),
+            }),
+        )
+    }
+
+    #[test]
+    fn updates_are_reduced_into_state() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .then(logger("a"))
+            .then(Action::update(
+                "nothing",
+                Box::new(|_| LogUpdate::default()),
+            ))
+            .then(logger("b"))
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let output = runner.run(
+            Log {
+                entries: vec!["start".to_string()],
+                last: "start".to_string(),
+            },
+            &RunContext::new(),
+        );
+        let output = output.completed().unwrap();
+
+        assert_eq!(output.entries, ["start", "a", "b"]);
+        assert_eq!(output.last, "b");
+    }
+
+    #[test]
+    fn reducing_join_merges_path_updates() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .fan_out(Join::reducing("merge logs"))
+            .path(|path| path.then(logger("a")))
+            .path(|path| path.then(logger("b")).then(logger("c")))
+            .join()
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let output = runner.run(
+            Log {
+                entries: vec!["start".to_string()],
+                last: "start".to_string(),
+            },
+            &RunContext::new(),
+        );
+        let output = output.completed().unwrap();
+
+        assert_eq!(output.entries, ["start", "a", "b", "c"]);
+        assert_eq!(output.last, "c");
+    }
+
+    #[test]
+    fn reducing_join_drops_changes_that_are_not_updates() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .fan_out(Join::reducing("merge logs"))
+            .path(|path| {
+                path.then(Action::new(
+                    "overwrite",
+                    Box::new(|log: Log| Log {
+                        entries: vec!["overwritten".to_string()],
+                        ..log
+                    }),
+                ))
+                .then(logger("a"))
+            })
+            .path(|path| path.then(logger("b")))
+            .join()
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let output = runner.run(
+            Log {
+                entries: vec!["start".to_string()],
+                last: "start".to_string(),
+            },
+            &RunContext::new(),
+        );
+        let output = output.completed().unwrap();
+
+        assert_eq!(output.entries, ["start", "a", "b"]);
+        assert_eq!(output.last, "b");
+    }
+
+    #[test]
+    fn nested_fan_out_passes_updates_to_reducing_join() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .fan_out(Join::reducing("merge logs"))
+            .path(|path| {
+                path.then(logger("a"))
+                    .fan_out(Join::new(
+                        "first",
+                        Box::new(|logs: Vec<Log>| logs.into_iter().next().unwrap()),
+                    ))
+                    .path(|path| path.then(logger("b")))
+                    .path(|path| path.then(logger("c")))
+                    .join()
+            })
+            .path(|path| path.then(logger("d")))
+            .join()
+            .terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+        let output = runner.run(
+            Log {
+                entries: vec!["start".to_string()],
+                last: "start".to_string(),
+            },
+            &RunContext::new(),
+        );
+        let output = output.completed().unwrap();
+
+        assert_eq!(output.entries, ["start", "a", "b", "c", "d"]);
+        assert_eq!(output.last, "d");
+    }
+}
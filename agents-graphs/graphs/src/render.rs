@@ -0,0 +1,228 @@
+use std::fmt::Write;
+
+use crate::{Graph, Node, NodeId};
+
+impl<T> Graph<T> {
+    /// Renders the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+
+        for node_id in self.sorted_node_ids() {
+            let node = self.node(node_id);
+
+            let shape = match node {
+                _ if node_id == self.start_id => "oval",
+                Node::Action(_) => "box",
+                Node::Branch(_) => "diamond",
+                Node::Router(_) => "hexagon",
+                Node::FanOut { .. } => "trapezium",
+                Node::Join(_) => "invtrapezium",
+                Node::Interrupt(_) => "parallelogram",
+                Node::Subgraph(_) => "box3d",
+                Node::Terminal => "doublecircle",
+            };
+
+            let _ = writeln!(
+                dot,
+                "    n{} [label=\"{}\", shape={shape}];",
+                node_id.0,
+                escape_dot(node.display_name())
+            );
+        }
+
+        for (from, to, label) in self.labelled_edges() {
+            match label {
+                Some(label) => {
+                    let _ = writeln!(
+                        dot,
+                        "    n{} -> n{} [label=\"{}\"];",
+                        from.0,
+                        to.0,
+                        escape_dot(label)
+                    );
+                }
+                None => {
+                    let _ = writeln!(dot, "    n{} -> n{};", from.0, to.0);
+                }
+            }
+        }
+
+        for (from, to) in self.fallback_edges() {
+            let _ = writeln!(
+                dot,
+                "    n{} -> n{} [label=\"fallback\", style=dashed];",
+                from.0, to.0
+            );
+        }
+
+        for (from, to) in self.all_goto_edges() {
+            let _ = writeln!(
+                dot,
+                "    n{} -> n{} [label=\"goto\", style=dotted];",
+                from.0, to.0
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+
+        for node_id in self.sorted_node_ids() {
+            let node = self.node(node_id);
+            let name = escape_mermaid(node.display_name());
+
+            let (open, close) = match node {
+                _ if node_id == self.start_id => ("([", "])"),
+                Node::Action(_) => ("[", "]"),
+                Node::Branch(_) => ("{", "}"),
+                Node::Router(_) => ("{{", "}}"),
+                Node::FanOut { .. } => ("[/", "\\]"),
+                Node::Join(_) => ("[\\", "/]"),
+                Node::Interrupt(_) => ("[/", "/]"),
+                Node::Subgraph(_) => ("[[", "]]"),
+                Node::Terminal => ("((", "))"),
+            };
+
+            let _ = writeln!(mermaid, "    n{}{open}\"{name}\"{close}", node_id.0);
+        }
+
+        for (from, to, label) in self.labelled_edges() {
+            match label {
+                Some(label) => {
+                    let _ = writeln!(
+                        mermaid,
+                        "    n{} -->|\"{}\"| n{}",
+                        from.0,
+                        escape_mermaid(label),
+                        to.0
+                    );
+                }
+                None => {
+                    let _ = writeln!(mermaid, "    n{} --> n{}", from.0, to.0);
+                }
+            }
+        }
+
+        for (from, to) in self.fallback_edges() {
+            let _ = writeln!(mermaid, "    n{} -.->|\"fallback\"| n{}", from.0, to.0);
+        }
+
+        for (from, to) in self.all_goto_edges() {
+            let _ = writeln!(mermaid, "    n{} -.->|\"goto\"| n{}", from.0, to.0);
+        }
+
+        mermaid
+    }
+
+    /// The goto targets of every action, ordered by the action they belong to.
+    fn all_goto_edges(&self) -> Vec<(NodeId, NodeId)> {
+        self.sorted_node_ids()
+            .into_iter()
+            .flat_map(|node_id| self.goto_edges(node_id).map(move |to| (node_id, to)))
+            .collect()
+    }
+
+    /// The fallbacks of node policies, ordered by the node they belong to.
+    fn fallback_edges(&self) -> Vec<(NodeId, NodeId)> {
+        let mut fallbacks = self
+            .policies
+            .iter()
+            .filter_map(|(node_id, policy)| Some((*node_id, policy.fallback?)))
+            .collect::<Vec<_>>();
+
+        fallbacks.sort();
+        fallbacks
+    }
+
+    /// Every edge in insertion order, labelled with its branch outcome or router key.
+    fn labelled_edges(&self) -> Vec<(NodeId, NodeId, Option<&str>)> {
+        self.edges
+            .iter()
+            .map(|edge| {
+                let label = match self.branch_outcome(edge) {
+                    Some(true) => Some("true"),
+                    Some(false) => Some("false"),
+                    None => edge.key.as_deref(),
+                };
+
+                (edge.from, edge.to, label)
+            })
+            .collect()
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::example_graph;
+
+    #[test]
+    fn renders_dot() {
+        let expected = r#"digraph {
+    n0 [label="START", shape=oval];
+    n1 [label="adder", shape=box];
+    n2 [label="is \"small\"", shape=diamond];
+    n3 [label="parity", shape=hexagon];
+    n4 [label="multiplier", shape=box];
+    n5 [label="Terminal", shape=doublecircle];
+    n6 [label="sum", shape=invtrapezium];
+    n7 [label="FanOut", shape=trapezium];
+    n8 [label="adder", shape=box];
+    n9 [label="Terminal", shape=doublecircle];
+    n0 -> n1;
+    n1 -> n2;
+    n2 -> n1 [label="true"];
+    n2 -> n3 [label="false"];
+    n3 -> n4 [label="0"];
+    n4 -> n5;
+    n3 -> n7 [label="1"];
+    n7 -> n8;
+    n8 -> n6;
+    n7 -> n6;
+    n6 -> n9;
+}
+"#;
+
+        assert_eq!(example_graph().to_dot(), expected);
+    }
+
+    #[test]
+    fn renders_mermaid() {
+        let expected = r#"flowchart TD
+    n0(["START"])
+    n1["adder"]
+    n2{"is #quot;small#quot;"}
+    n3{{"parity"}}
+    n4["multiplier"]
+    n5(("Terminal"))
+    n6[\"sum"/]
+    n7[/"FanOut"\]
+    n8["adder"]
+    n9(("Terminal"))
+    n0 --> n1
+    n1 --> n2
+    n2 -->|"true"| n1
+    n2 -->|"false"| n3
+    n3 -->|"0"| n4
+    n4 --> n5
+    n3 -->|"1"| n7
+    n7 --> n8
+    n8 --> n6
+    n7 --> n6
+    n6 --> n9
+"#;
+
+        assert_eq!(example_graph().to_mermaid(), expected);
+    }
+}
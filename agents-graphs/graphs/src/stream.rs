@@ -0,0 +1,125 @@
+use futures::{
+    Stream, StreamExt,
+    channel::mpsc,
+    executor::{self, BlockingStream},
+    future, stream,
+};
+
+use crate::{GraphRunner, NodeId, RunContext, RunError, RunOutcome, RunScope, limits::RunTracker};
+
+/// What a streamed run reports as it executes.
+#[derive(Debug)]
+pub enum RunEvent<T> {
+    /// A node completed, producing `state`.
+    Node {
+        node_id: NodeId,
+        display_name: String,
+        state: T,
+    },
+    /// The run ended. Always the last event.
+    Finished(Result<RunOutcome<T>, RunError<T>>),
+}
+
+impl<T: Clone + Send + Sync> GraphRunner<T> {
+    /// Runs the graph, yielding an event after every node and a final event with the result.
+    ///
+    /// The run makes progress only while the stream is polled. Fan-out paths run concurrently,
+    /// so their events interleave.
+    pub fn run_stream<'a>(
+        &'a self,
+        input: T,
+        context: &'a RunContext,
+    ) -> impl Stream<Item = RunEvent<T>> + 'a {
+        let (events, receiver) = mpsc::unbounded();
+
+        let run = async move {
+            let tracker = RunTracker::new(self.limits);
+            let scope = RunScope {
+                events: Some(&events),
+                ..RunScope::new(&tracker, context)
+            };
+
+            let result = self.run_to_end(self.graph.start_id, input, &scope).await;
+
+            // the receiver is only gone if the caller dropped the stream
+            events.unbounded_send(RunEvent::Finished(result)).ok();
+        };
+
+        // the run yields nothing itself; its events arrive through the channel,
+        // which closes once the run is done with it
+        let run = stream::once(run).filter_map(|()| future::ready(None));
+
+        stream::select(receiver, run)
+    }
+
+    /// Like `run_stream`, running each node on the current thread as the iterator is advanced.
+    pub fn run_iter<'a>(
+        &'a self,
+        input: T,
+        context: &'a RunContext,
+    ) -> BlockingStream<impl Stream<Item = RunEvent<T>> + Unpin + 'a> {
+        executor::block_on_stream(Box::pin(self.run_stream(input, context)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Graph;
+    use crate::test_support::{adder, model_then_tool, multiplier};
+
+    #[test]
+    fn run_iter_yields_event_after_each_node() {
+        let mut graph = Graph::new();
+        graph.start().then(adder(1)).then(multiplier(3)).terminate();
+
+        let runner = GraphRunner::new(graph).unwrap();
+
+        let events = runner.run_iter(1, &RunContext::new()).collect::<Vec<_>>();
+
+        let nodes = events
+            .iter()
+            .filter_map(|event| match event {
+                RunEvent::Node {
+                    display_name,
+                    state,
+                    ..
+                } => Some((display_name.as_str(), *state)),
+                RunEvent::Finished(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            nodes,
+            [
+                ("START", 1),
+                ("adder", 2),
+                ("multiplier", 6),
+                ("Terminal", 6)
+            ]
+        );
+        assert!(matches!(
+            events.last(),
+            Some(RunEvent::Finished(Ok(RunOutcome::Completed(6))))
+        ));
+    }
+
+    #[test]
+    fn run_stream_ends_with_failure() {
+        let runner = GraphRunner::new(model_then_tool(adder(1))).unwrap();
+
+        let events = futures::executor::block_on(futures::StreamExt::collect::<Vec<_>>(
+            runner.run_stream(2, &RunContext::new()),
+        ));
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[1],
+            RunEvent::Node { display_name, state: 3, .. } if display_name == "adder"
+        ));
+        assert!(matches!(
+            &events[2],
+            RunEvent::Finished(Err(e)) if e.display_name() == "tool"
+        ));
+    }
+}
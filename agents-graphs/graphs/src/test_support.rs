@@ -0,0 +1,231 @@
+//! Graphs and actions shared by the tests of several modules.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::{
+    Action, Command, Condition, Graph, Join, NodeId, Observer, Router, RunError, RunKey, RunOutcome,
+};
+
+pub fn adder(add: i32) -> Action<i32> {
+    Action::new("adder", Box::new(move |x| x + add))
+}
+
+pub fn subtractor(subtract: i32) -> Action<i32> {
+    Action::new("subtractor", Box::new(move |x| x - subtract))
+}
+
+pub fn multiplier(multiply: i32) -> Action<i32> {
+    Action::new("multiplier", Box::new(move |x| x * multiply))
+}
+
+pub fn sum() -> Join<i32> {
+    Join::new("sum", Box::new(|results| results.into_iter().sum()))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Size {
+    Small,
+    Medium,
+    Large,
+}
+
+impl From<Size> for String {
+    fn from(size: Size) -> Self {
+        format!("{size:?}")
+    }
+}
+
+pub fn size_router() -> Router<i32> {
+    Router::new(
+        "size",
+        Box::new(|&x| match x {
+            ..10 => Size::Small,
+            10..100 => Size::Medium,
+            _ => Size::Large,
+        }),
+    )
+}
+
+pub fn example_graph() -> Graph<i32> {
+    let mut graph = Graph::new();
+
+    let loop_start = graph.register_node(adder(1));
+
+    graph.start().then(loop_start).branch(
+        Condition::new("is \"small\"", Box::new(|&x| x < 10)),
+        |graph| {
+            graph.then(loop_start);
+        },
+        |graph| {
+            graph
+                .route(Router::new("parity", Box::new(|x| format!("{}", x % 2))))
+                .on("0", |graph| graph.then(multiplier(2)).terminate())
+                .on("1", |graph| {
+                    graph
+                        .fan_out(sum())
+                        .path(|path| path.then(adder(1)))
+                        .path(|path| path)
+                        .join()
+                        .terminate();
+                });
+        },
+    );
+
+    graph
+}
+
+#[derive(Default)]
+pub struct RecordingObserver {
+    pub events: std::sync::Mutex<Vec<String>>,
+}
+
+impl RecordingObserver {
+    fn record(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl Observer<i32> for RecordingObserver {
+    fn on_node_start(&self, _run: RunKey, node_id: NodeId, display_name: &str, state: &i32) {
+        self.record(format!("start {node_id:?} {display_name} {state}"));
+    }
+
+    fn on_node_end(
+        &self,
+        _run: RunKey,
+        node_id: NodeId,
+        display_name: &str,
+        state: &i32,
+        _elapsed: std::time::Duration,
+    ) {
+        self.record(format!("end {node_id:?} {display_name} {state}"));
+    }
+
+    fn on_branch(&self, _run: RunKey, _node_id: NodeId, display_name: &str, taken: bool) {
+        self.record(format!("branch {display_name} {taken}"));
+    }
+
+    fn on_route(&self, _run: RunKey, _node_id: NodeId, display_name: &str, key: &str) {
+        self.record(format!("route {display_name} {key}"));
+    }
+
+    fn on_run_end(&self, _run: RunKey, result: Result<&RunOutcome<i32>, &RunError<i32>>) {
+        match result {
+            Ok(RunOutcome::Completed(state)) => self.record(format!("done {state}")),
+            Ok(RunOutcome::Interrupted(interrupted)) => {
+                self.record(format!("interrupted {:?}", interrupted.node_id));
+            }
+            Err(e) => self.record(format!("failed {}", e.display_name())),
+        }
+    }
+}
+
+pub fn counting_loop(graph: &mut Graph<i32>) {
+    let loop_start = graph.register_node(adder(1));
+
+    graph.start().then(loop_start).branch(
+        Condition::new("below_100", Box::new(|&x| x < 100)),
+        |graph| {
+            graph.then(loop_start);
+        },
+        |graph| {
+            graph.terminate();
+        },
+    );
+}
+
+pub fn flaky_graph(failing: &Arc<AtomicBool>) -> Graph<i32> {
+    let failing = failing.clone();
+    let mut graph = Graph::new();
+
+    graph
+        .start()
+        .then(adder(1))
+        .then(Action::new_fallible(
+            "flaky",
+            Box::new(move |x| {
+                if failing.load(Ordering::SeqCst) {
+                    Err("flaked".into())
+                } else {
+                    Ok(x)
+                }
+            }),
+        ))
+        .then(multiplier(3))
+        .terminate();
+
+    graph
+}
+
+pub fn fails_first(failures: usize) -> (Action<i32>, Arc<AtomicUsize>) {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+
+    let action = Action::new_fallible(
+        "fails_first",
+        Box::new(move |x| {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst) + 1;
+
+            if attempt <= failures {
+                Err(format!("attempt {attempt} failed").into())
+            } else {
+                Ok(x)
+            }
+        }),
+    );
+
+    (action, attempts)
+}
+
+pub fn model_then_tool(model: Action<i32>) -> Graph<i32> {
+    let mut graph = Graph::new();
+
+    graph
+        .start()
+        .then(model)
+        .then(Action::new_fallible(
+            "tool",
+            Box::new(|x: i32| {
+                if x % 2 == 0 {
+                    Ok(x)
+                } else {
+                    Err(format!("{x} is odd").into())
+                }
+            }),
+        ))
+        .terminate();
+
+    graph
+}
+
+pub fn is_even() -> Condition<i32> {
+    Condition::new("is_even", Box::new(|x| x % 2 == 0))
+}
+
+/// start -> triage -> adder -> Terminal, where triage jumps to "double" for even numbers.
+pub fn handoff_graph(triage: Action<i32>) -> Graph<i32> {
+    let mut graph = Graph::new();
+
+    graph.start().then(triage).then(adder(1)).terminate();
+    let double = graph.register_node(Action::new("double", Box::new(|x| x * 2)));
+    graph.continue_from(double).terminate();
+
+    graph
+}
+
+pub fn triage() -> Action<i32> {
+    Action::new_goto(
+        "triage",
+        vec!["double".into()],
+        Box::new(|x| {
+            if x % 2 == 0 {
+                Command::goto(x, "double")
+            } else {
+                Command::next(x)
+            }
+        }),
+    )
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{NodeId, Observer, RunError, RunKey, RunOutcome};
+
+/// A record of a single run, as seen by a `TraceRecorder`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace<T> {
+    /// The node the run started from.
+    pub start: NodeId,
+    pub input: T,
+    /// Every node that completed, in the order it completed.
+    pub steps: Vec<TraceStep<T>>,
+    /// How the run ended, if it has.
+    pub outcome: Option<TraceOutcome<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep<T> {
+    pub node_id: NodeId,
+    pub display_name: String,
+    /// The state the node produced.
+    pub state: T,
+    pub elapsed: Duration,
+    /// Whether the true branch was taken, for branch nodes.
+    pub branch: Option<bool>,
+    /// The key that was returned, for router nodes.
+    pub route: Option<String>,
+    /// The node jumped to, for actions that did.
+    pub goto: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceOutcome<T> {
+    Completed(T),
+    Interrupted {
+        node_id: NodeId,
+        state: T,
+    },
+    Failed {
+        node_id: NodeId,
+        display_name: String,
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+enum Choice {
+    Branch(bool),
+    Route(String),
+    Goto(NodeId),
+}
+
+/// A run that has started but not yet ended.
+#[derive(Debug)]
+struct Recording<T> {
+    trace: Trace<T>,
+    /// Branch, route and goto outcomes, until the node they belong to completes.
+    choices: HashMap<NodeId, Choice>,
+}
+
+#[derive(Debug)]
+struct Recordings<T> {
+    running: HashMap<RunKey, Recording<T>>,
+    /// The trace of the run that ended last.
+    latest: Option<Trace<T>>,
+}
+
+/// An observer that records runs as `Trace`s, keeping the most recent one.
+///
+/// Runs that execute at the same time, e.g. on a runner shared between requests,
+/// are recorded separately.
+#[derive(Debug)]
+pub struct TraceRecorder<T> {
+    recordings: Mutex<Recordings<T>>,
+}
+
+impl<T> TraceRecorder<T> {
+    pub fn new() -> Self {
+        Self {
+            recordings: Mutex::new(Recordings {
+                running: HashMap::new(),
+                latest: None,
+            }),
+        }
+    }
+
+    /// The trace of the run that ended last, or while no run has ended, of the run that
+    /// started last. `None` if nothing has run yet.
+    pub fn trace(&self) -> Option<Trace<T>>
+    where
+        T: Clone,
+    {
+        let recordings = self.lock();
+
+        recordings.latest.clone().or_else(|| {
+            recordings
+                .running
+                .iter()
+                .max_by_key(|(run, _)| **run)
+                .map(|(_, recording)| recording.trace.clone())
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recordings<T>> {
+        self.recordings
+            .lock()
+            .expect("trace recorder lock poisoned")
+    }
+
+    fn choose(&self, run: RunKey, node_id: NodeId, choice: Choice) {
+        if let Some(recording) = self.lock().running.get_mut(&run) {
+            recording.choices.insert(node_id, choice);
+        }
+    }
+}
+
+impl<T> Default for TraceRecorder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone + Send> Observer<T> for TraceRecorder<T> {
+    fn on_node_start(&self, run: RunKey, node_id: NodeId, _display_name: &str, state: &T) {
+        // the first node of a run starts its trace
+        self.lock().running.entry(run).or_insert_with(|| Recording {
+            trace: Trace {
+                start: node_id,
+                input: state.clone(),
+                steps: Vec::new(),
+                outcome: None,
+            },
+            choices: HashMap::new(),
+        });
+    }
+
+    fn on_node_end(
+        &self,
+        run: RunKey,
+        node_id: NodeId,
+        display_name: &str,
+        state: &T,
+        elapsed: Duration,
+    ) {
+        if let Some(recording) = self.lock().running.get_mut(&run) {
+            let (branch, route, goto) = match recording.choices.remove(&node_id) {
+                Some(Choice::Branch(taken)) => (Some(taken), None, None),
+                Some(Choice::Route(key)) => (None, Some(key), None),
+                Some(Choice::Goto(target)) => (None, None, Some(target)),
+                None => (None, None, None),
+            };
+
+            recording.trace.steps.push(TraceStep {
+                node_id,
+                display_name: display_name.to_string(),
+                state: state.clone(),
+                elapsed,
+                branch,
+                route,
+                goto,
+            });
+        }
+    }
+
+    fn on_branch(&self, run: RunKey, node_id: NodeId, _display_name: &str, taken: bool) {
+        self.choose(run, node_id, Choice::Branch(taken));
+    }
+
+    fn on_route(&self, run: RunKey, node_id: NodeId, _display_name: &str, key: &str) {
+        self.choose(run, node_id, Choice::Route(key.to_string()));
+    }
+
+    fn on_goto(&self, run: RunKey, node_id: NodeId, _display_name: &str, target: NodeId) {
+        self.choose(run, node_id, Choice::Goto(target));
+    }
+
+    fn on_run_end(&self, run: RunKey, result: Result<&RunOutcome<T>, &RunError<T>>) {
+        let outcome = match result {
+            Ok(RunOutcome::Completed(state)) => TraceOutcome::Completed(state.clone()),
+            Ok(RunOutcome::Interrupted(interrupted)) => TraceOutcome::Interrupted {
+                node_id: interrupted.node_id,
+                state: interrupted.state.clone(),
+            },
+            Err(e) => TraceOutcome::Failed {
+                node_id: e.node_id(),
+                display_name: e.display_name().to_string(),
+                error: e.kind().to_string(),
+            },
+        };
+
+        let mut recordings = self.lock();
+
+        if let Some(mut recording) = recordings.running.remove(&run) {
+            recording.trace.outcome = Some(outcome);
+            recordings.latest = Some(recording.trace);
+        }
+    }
+}
+
+/// The recorded steps of a trace being replayed, in order for each node.
+#[derive(Debug)]
+pub struct Replay<T> {
+    steps: Mutex<HashMap<NodeId, VecDeque<TraceStep<T>>>>,
+}
+
+impl<T: Clone> Replay<T> {
+    pub fn new(trace: &Trace<T>) -> Self {
+        let mut steps = HashMap::<_, VecDeque<_>>::new();
+
+        for step in &trace.steps {
+            steps
+                .entry(step.node_id)
+                .or_default()
+                .push_back(step.clone());
+        }
+
+        Self {
+            steps: Mutex::new(steps),
+        }
+    }
+
+    /// The next recorded step of the node, if it has any left.
+    pub fn next(&self, node_id: NodeId) -> Option<TraceStep<T>> {
+        self.steps
+            .lock()
+            .expect("replay lock poisoned")
+            .get_mut(&node_id)?
+            .pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_support::{adder, counting_loop, handoff_graph, model_then_tool, triage};
+    use crate::{Action, Graph, GraphRunner, ResumeError, RunContext, RunErrorKind};
+
+    #[test]
+    fn trace_records_nodes_branches_and_outcome() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let recorder = std::sync::Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        assert_eq!(
+            runner.run(98, &RunContext::new()),
+            RunOutcome::Completed(100)
+        );
+
+        let trace = recorder.trace().unwrap();
+
+        let steps = trace
+            .steps
+            .iter()
+            .map(|step| (step.display_name.as_str(), step.state, step.branch))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            steps,
+            [
+                ("START", 98, None),
+                ("adder", 99, None),
+                ("below_100", 99, Some(true)),
+                ("adder", 100, None),
+                ("below_100", 100, Some(false)),
+                ("Terminal", 100, None),
+            ]
+        );
+        assert_eq!(trace.input, 98);
+        assert_eq!(trace.outcome, Some(TraceOutcome::Completed(100)));
+
+        let json = serde_json::to_string(&trace).unwrap();
+        assert_eq!(serde_json::from_str::<Trace<i32>>(&json).unwrap(), trace);
+
+        // the next run replaces the trace
+        runner.run(99, &RunContext::new());
+        assert_eq!(recorder.trace().unwrap().input, 99);
+    }
+
+    /// An action that yields to the executor once, so runs polled together interleave.
+    fn yielding() -> Action<i32> {
+        Action::new_async(
+            "yielding",
+            Box::new(|x| {
+                let mut yielded = false;
+
+                Box::pin(async move {
+                    futures::future::poll_fn(|cx| {
+                        if yielded {
+                            std::task::Poll::Ready(())
+                        } else {
+                            yielded = true;
+                            cx.waker().wake_by_ref();
+                            std::task::Poll::Pending
+                        }
+                    })
+                    .await;
+
+                    Ok(x)
+                })
+            }),
+        )
+    }
+
+    #[test]
+    fn trace_keeps_concurrent_runs_apart() {
+        let mut graph = Graph::new();
+        graph.start().then(yielding()).then(adder(2)).terminate();
+
+        let recorder = Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        let (first, second) = futures::executor::block_on(futures::future::join(
+            runner.try_run_async(0, &RunContext::new()),
+            runner.try_run_async(100, &RunContext::new()),
+        ));
+        assert_eq!(
+            (first.unwrap(), second.unwrap()),
+            (RunOutcome::Completed(2), RunOutcome::Completed(102))
+        );
+
+        // the run that ended last, with only its own steps
+        let trace = recorder.trace().unwrap();
+        let states = trace
+            .steps
+            .iter()
+            .map(|step| step.state)
+            .collect::<Vec<_>>();
+
+        assert_eq!(trace.input, 100);
+        assert_eq!(states, [100, 100, 102, 102]);
+        assert_eq!(trace.outcome, Some(TraceOutcome::Completed(102)));
+    }
+
+    #[test]
+    fn replay_reproduces_failure_without_recorded_nodes() {
+        let recorder = std::sync::Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(model_then_tool(adder(1)))
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        assert!(runner.try_run(2, &RunContext::new()).is_err());
+
+        let trace = recorder.trace().unwrap();
+        assert!(matches!(
+            &trace.outcome,
+            Some(TraceOutcome::Failed { display_name, .. }) if display_name == "tool"
+        ));
+
+        // the model is not available when replaying
+        let offline_model = Action::new("model", Box::new(|_| panic!("model was called")));
+        let runner = GraphRunner::new(model_then_tool(offline_model)).unwrap();
+
+        let Err(ResumeError::Run(err)) = runner.try_replay(&trace, &RunContext::new()) else {
+            panic!("replay should fail at the tool");
+        };
+
+        assert_eq!(err.display_name(), "tool");
+        assert_eq!(err.into_state(), 3);
+    }
+
+    #[test]
+    fn replay_follows_recorded_branches() {
+        let mut graph = Graph::new();
+        counting_loop(&mut graph);
+
+        let recorder = std::sync::Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(graph)
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        runner.run(98, &RunContext::new());
+        let mut trace = recorder.trace().unwrap();
+
+        // the recorded outcome is followed, not the live condition
+        trace.steps[2].branch = Some(false);
+
+        assert_eq!(
+            runner.try_replay(&trace, &RunContext::new()).unwrap(),
+            RunOutcome::Completed(99)
+        );
+    }
+
+    #[test]
+    fn replay_follows_recorded_gotos() {
+        let recorder = std::sync::Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(handoff_graph(triage()))
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        assert_eq!(runner.run(2, &RunContext::new()), RunOutcome::Completed(4));
+
+        let trace = recorder.trace().unwrap();
+
+        // replayed actions are not invoked, so triage cannot decide again
+        let offline_triage = Action::new_goto(
+            "triage",
+            vec!["double".into()],
+            Box::new(|_| panic!("triage was called")),
+        );
+        let runner = GraphRunner::new(handoff_graph(offline_triage)).unwrap();
+
+        assert_eq!(
+            runner.try_replay(&trace, &RunContext::new()).unwrap(),
+            RunOutcome::Completed(4)
+        );
+    }
+
+    #[test]
+    fn replay_rejects_nodes_missing_from_the_graph() {
+        let recorder = std::sync::Arc::new(TraceRecorder::new());
+        let runner = GraphRunner::new(handoff_graph(triage()))
+            .unwrap()
+            .with_observer(Box::new(recorder.clone()));
+
+        runner.run(2, &RunContext::new());
+        let trace = recorder.trace().unwrap();
+
+        let mut unknown_start = trace.clone();
+        unknown_start.start = NodeId(42);
+
+        assert!(matches!(
+            runner.try_replay(&unknown_start, &RunContext::new()),
+            Err(ResumeError::UnknownNode(NodeId(42)))
+        ));
+
+        let mut unknown_goto = trace;
+        let step = unknown_goto
+            .steps
+            .iter_mut()
+            .find(|step| step.goto.is_some())
+            .unwrap();
+        step.goto = Some(NodeId(77));
+
+        let Err(ResumeError::Run(err)) = runner.try_replay(&unknown_goto, &RunContext::new())
+        else {
+            panic!("replay should fail at the recorded goto");
+        };
+
+        assert_eq!(err.display_name(), "triage");
+        assert!(matches!(err.kind(), RunErrorKind::InvalidGoto(target) if target == "NodeId(77)"));
+    }
+}
@@ -0,0 +1,402 @@
+use std::{collections::HashSet, error::Error, fmt::Display};
+
+use crate::{Goto, Graph, Node, NodeId};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    /// `Graph::start` was never called.
+    NotStarted,
+    /// `Graph::start` was called more than once.
+    StartedMoreThanOnce(usize),
+    /// The node has fewer outgoing edges than it needs.
+    MissingEdges { expected: usize, found: usize },
+    /// The node has more outgoing edges than it can follow.
+    ExtraEdges { expected: usize, found: usize },
+    /// An edge leaving a router has no key.
+    UnkeyedRouterEdge,
+    /// The join of a fan-out is not a join node.
+    NotAJoin(NodeId),
+    /// An edge points at a node that is not part of this graph.
+    UnknownTarget(NodeId),
+    /// An edge leaves a node that is not part of this graph.
+    UnknownSource(NodeId),
+    /// A goto target names no node, or more than one.
+    UnresolvedGoto { name: String, matches: usize },
+    /// The node cannot be reached from the start node.
+    Unreachable,
+    /// An interrupt can be reached from the paths of the fan-out, where the run cannot stop.
+    InterruptInFanOut(NodeId),
+}
+
+impl Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotStarted => write!(f, "start() was never called"),
+            Self::StartedMoreThanOnce(count) => write!(f, "start() was called {count} times"),
+            Self::MissingEdges { expected, found } | Self::ExtraEdges { expected, found } => {
+                write!(f, "expected {expected} outgoing edge(s), found {found}")
+            }
+            Self::UnkeyedRouterEdge => write!(f, "router edge has no key"),
+            Self::NotAJoin(join) => write!(f, "fan-out join {join:?} is not a join node"),
+            Self::UnknownTarget(target) => write!(f, "edge to unknown node {target:?}"),
+            Self::UnknownSource(source) => write!(f, "edge from unknown node {source:?}"),
+            Self::UnresolvedGoto { name, matches } => {
+                write!(f, "goto target {name} matches {matches} nodes")
+            }
+            Self::Unreachable => write!(f, "unreachable from the start node"),
+            Self::InterruptInFanOut(fan_out) => {
+                write!(f, "interrupt inside the paths of fan-out {fan_out:?}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub node_id: NodeId,
+    pub display_name: String,
+    pub kind: IssueKind,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "node {:?} ({}): {}",
+            self.node_id, self.display_name, self.kind
+        )
+    }
+}
+
+/// Every structural problem found in a graph, ordered by node id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationError {
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "graph has {} problem(s):", self.issues.len())?;
+
+        for issue in &self.issues {
+            write!(f, "\n  {issue}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+impl<T> Graph<T> {
+    /// Checks the structure of the graph, reporting every problem found rather than the first.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut issues = Vec::new();
+
+        let mut issue = |node_id: NodeId, kind: IssueKind| {
+            let display_name = self
+                .nodes
+                .get(&node_id)
+                .map_or("<unknown>", |node| node.node.display_name())
+                .to_string();
+
+            issues.push(ValidationIssue {
+                node_id,
+                display_name,
+                kind,
+            });
+        };
+
+        match self.start_count {
+            0 => issue(self.start_id, IssueKind::NotStarted),
+            1 => {}
+            count => issue(self.start_id, IssueKind::StartedMoreThanOnce(count)),
+        }
+
+        if let Some(fallback) = self.limits_fallback
+            && !self.nodes.contains_key(&fallback)
+        {
+            issue(self.start_id, IssueKind::UnknownTarget(fallback));
+        }
+
+        for (node_id, policy) in &self.policies {
+            if let Some(fallback) = policy.fallback
+                && !self.nodes.contains_key(&fallback)
+            {
+                issue(*node_id, IssueKind::UnknownTarget(fallback));
+            }
+        }
+
+        for edge in &self.edges {
+            if !self.nodes.contains_key(&edge.to) {
+                issue(edge.from, IssueKind::UnknownTarget(edge.to));
+            }
+
+            if !self.nodes.contains_key(&edge.from) {
+                issue(edge.to, IssueKind::UnknownSource(edge.from));
+            }
+        }
+
+        self.check_goto_targets(&mut issue);
+
+        let reachable = self.reachable_from(self.roots());
+
+        for node_id in self.sorted_node_ids() {
+            let found = self.edges_from(node_id).count();
+
+            let expected = match self.node(node_id) {
+                Node::Action(_) | Node::Join(_) | Node::Interrupt(_) | Node::Subgraph(_) => Some(1),
+                Node::Branch(_) => Some(2),
+                Node::Terminal => Some(0),
+                Node::Router(_) => {
+                    if self.edges_from(node_id).any(|edge| edge.key.is_none()) {
+                        issue(node_id, IssueKind::UnkeyedRouterEdge);
+                    }
+
+                    None
+                }
+                Node::FanOut { join } => {
+                    if !matches!(self.nodes.get(join).map(|n| &n.node), Some(Node::Join(_))) {
+                        issue(node_id, IssueKind::NotAJoin(*join));
+                    }
+
+                    for path_node in self.path_nodes(node_id, *join) {
+                        if let Some(Node::Interrupt(_)) =
+                            self.nodes.get(&path_node).map(|n| &n.node)
+                        {
+                            issue(path_node, IssueKind::InterruptInFanOut(node_id));
+                        }
+                    }
+
+                    None
+                }
+            };
+
+            match expected {
+                Some(expected) if found < expected => {
+                    issue(node_id, IssueKind::MissingEdges { expected, found });
+                }
+                Some(expected) if found > expected => {
+                    issue(node_id, IssueKind::ExtraEdges { expected, found });
+                }
+                // routers and fan-outs need at least one edge
+                None if found == 0 => {
+                    issue(node_id, IssueKind::MissingEdges { expected: 1, found });
+                }
+                _ => {}
+            }
+
+            if !reachable.contains(&node_id) {
+                issue(node_id, IssueKind::Unreachable);
+            }
+        }
+
+        // policies are kept in a map, and path nodes in a set, so their issues arrive
+        // in no particular order
+        issues.sort_by_key(|issue| issue.node_id);
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { issues })
+        }
+    }
+
+    fn check_goto_targets(&self, issue: &mut impl FnMut(NodeId, IssueKind)) {
+        for node_id in self.sorted_node_ids() {
+            let Node::Action(action) = self.node(node_id) else {
+                continue;
+            };
+
+            for target in &action.goto_targets {
+                match (target, self.goto_candidates(target).len()) {
+                    (_, 1) => {}
+                    (Goto::Node(target), _) => issue(node_id, IssueKind::UnknownTarget(*target)),
+                    (Goto::Named(name), matches) => issue(
+                        node_id,
+                        IssueKind::UnresolvedGoto {
+                            name: name.clone(),
+                            matches,
+                        },
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Every node the paths of a fan-out can run, up to its join.
+    fn path_nodes(&self, fan_out_id: NodeId, join: NodeId) -> HashSet<NodeId> {
+        let mut path_nodes = HashSet::new();
+        let mut pending = self.next_nodes(fan_out_id).collect::<Vec<_>>();
+
+        while let Some(node_id) = pending.pop() {
+            if node_id != join && path_nodes.insert(node_id) {
+                pending.extend(self.successors(node_id));
+            }
+        }
+
+        path_nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{adder, handoff_graph, subtractor, sum};
+    use crate::{Action, Command, Condition, GraphRunner, Interrupt, NodePolicy};
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let mut graph = Graph::new();
+
+        let unreachable = graph.register_node(subtractor(1));
+        graph.add_node_from(unreachable, Node::Terminal);
+
+        graph.start().then(adder(1));
+        graph.start().branch(
+            Condition::new("is_even", Box::new(|x| x % 2 == 0)),
+            |graph| {
+                graph.terminate();
+            },
+            |_| {},
+        );
+
+        let err = graph.validate().unwrap_err();
+
+        let issues = err
+            .issues()
+            .iter()
+            .map(|issue| {
+                (
+                    issue.node_id,
+                    issue.display_name.as_str(),
+                    issue.kind.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            issues,
+            vec![
+                (NodeId(0), "START", IssueKind::StartedMoreThanOnce(2)),
+                (
+                    NodeId(0),
+                    "START",
+                    IssueKind::ExtraEdges {
+                        expected: 1,
+                        found: 2
+                    }
+                ),
+                (NodeId(1), "subtractor", IssueKind::Unreachable),
+                (NodeId(2), "Terminal", IssueKind::Unreachable),
+                (
+                    NodeId(3),
+                    "adder",
+                    IssueKind::MissingEdges {
+                        expected: 1,
+                        found: 0
+                    }
+                ),
+                (
+                    NodeId(4),
+                    "is_even",
+                    IssueKind::MissingEdges {
+                        expected: 2,
+                        found: 1
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_orders_issues_by_node_id() {
+        let mut graph = Graph::new();
+
+        let unknown = NodeId(100);
+        let mut path = graph.start();
+        for _ in 0..10 {
+            path = path.then(adder(1)).with_policy(NodePolicy {
+                fallback: Some(unknown),
+                ..NodePolicy::default()
+            });
+        }
+        path.terminate();
+
+        let err = graph.validate().unwrap_err();
+
+        let node_ids = err
+            .issues()
+            .iter()
+            .map(|issue| issue.node_id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(node_ids, (1..=10).map(NodeId).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn validate_reports_missing_start() {
+        let graph = Graph::<i32>::new();
+
+        let err = graph.validate().unwrap_err();
+
+        assert_eq!(err.issues().len(), 1);
+        assert_eq!(err.issues()[0].kind, IssueKind::NotStarted);
+    }
+
+    #[test]
+    fn runner_rejects_invalid_graph() {
+        let mut graph = Graph::new();
+
+        graph.start().then(adder(1));
+
+        let err = GraphRunner::new(graph).err().unwrap();
+
+        assert_eq!(err.issues()[0].display_name, "adder");
+        assert_eq!(
+            err.to_string(),
+            "graph has 1 problem(s):\n  node NodeId(1) (adder): expected 1 outgoing edge(s), found 0"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_interrupts_in_fan_out_paths() {
+        let mut graph = Graph::new();
+
+        graph
+            .start()
+            .fan_out(sum())
+            .path(|path| path.then(adder(1)))
+            .path(|path| path.then(Interrupt::new("approve")))
+            .join()
+            .terminate();
+
+        let err = graph.validate().unwrap_err();
+
+        assert_eq!(err.issues().len(), 1);
+        assert_eq!(err.issues()[0].display_name, "approve");
+        assert_eq!(
+            err.issues()[0].kind,
+            IssueKind::InterruptInFanOut(NodeId(2))
+        );
+    }
+
+    #[test]
+    fn validation_reports_unresolved_goto_targets() {
+        let triage = Action::new_goto("triage", vec!["missing".into()], Box::new(Command::next));
+
+        let err = handoff_graph(triage).validate().unwrap_err();
+
+        assert!(err.issues().iter().any(|issue| issue.kind
+            == IssueKind::UnresolvedGoto {
+                name: "missing".to_string(),
+                matches: 0
+            }));
+    }
+}
@@ -1,3 +1,4 @@
+use graphs::BoxError;
 use graphs_ai::model::{ChatCompletionResponse, ModelClient};
 use log::{debug, info};
 use serde_json::Value;
@@ -27,7 +28,7 @@ impl ModelClient for OpenAIModel {
     fn get_model_response(
         &self,
         request: &graphs_ai::model::ChatCompletionRequest,
-    ) -> graphs_ai::model::ChatCompletionResponse {
+    ) -> Result<ChatCompletionResponse, BoxError> {
         // let request: OpenAIChatCompletionRequest = convert_request(request, &self.model);
 
         let client = reqwest::blocking::Client::new();
@@ -39,7 +40,7 @@ impl ModelClient for OpenAIModel {
             url, self.model
         );
 
-        let body = serde_json::to_string(&request).expect("Failed to serialize request body");
+        let body = serde_json::to_string(&request)?;
 
         if log::log_enabled!(log::Level::Debug) {
             debug!("Request body: {body}");
@@ -50,18 +51,17 @@ impl ModelClient for OpenAIModel {
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .json(&request)
-            .send()
-            .expect("Failed to send request to OpenAI");
+            .send()?
+            .error_for_status()?;
 
-        let response_json: Value = response.json().expect("Failed to parse OpenAI response");
+        let response_json: Value = response.json()?;
 
         debug!("Response: {response_json}");
 
-        let openai_response = serde_json::from_value::<ChatCompletionResponse>(response_json)
-            .expect("Failed to parse OpenAI response");
+        let openai_response = serde_json::from_value::<ChatCompletionResponse>(response_json)?;
 
         debug!("parsed openai response: {openai_response:?}");
 
-        openai_response
+        Ok(openai_response)
     }
 }